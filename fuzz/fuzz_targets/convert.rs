@@ -0,0 +1,68 @@
+//! `split_rgb_channels` and `jxl_to_rgba8` with channel counts and sizes that
+//! do not necessarily match the image, as a misbehaving decoder could return
+
+#![no_main]
+
+use arbitrary::Arbitrary;
+use jxl::api::JxlColorType;
+use jxl::image::Image;
+use jxl_ui::decoder::rgb_conversion::jxl_to_rgba8;
+use jxl_ui::decoder::worker::split_rgb_channels;
+use libfuzzer_sys::fuzz_target;
+
+#[derive(Arbitrary, Debug)]
+enum ColorType {
+    Grayscale,
+    GrayscaleAlpha,
+    Rgb,
+    Rgba,
+    Bgr,
+    Bgra,
+}
+
+#[derive(Arbitrary, Debug)]
+struct Input {
+    color_type: ColorType,
+    width: u8,
+    height: u8,
+    /// Interleaved RGB buffer size, in samples per row and rows
+    interleaved: (u8, u8),
+    /// Sizes of the extra (alpha) channels
+    extra: Vec<(u8, u8)>,
+    value: f32,
+}
+
+fn image(size: (usize, usize), value: f32) -> Option<Image<f32>> {
+    let mut image = Image::<f32>::new(size).ok()?;
+    image.fill(value);
+    Some(image)
+}
+
+fuzz_target!(|input: Input| {
+    let (width, height) = (input.width as usize, input.height as usize);
+    let color_type = match input.color_type {
+        ColorType::Grayscale => JxlColorType::Grayscale,
+        ColorType::GrayscaleAlpha => JxlColorType::GrayscaleAlpha,
+        ColorType::Rgb => JxlColorType::Rgb,
+        ColorType::Rgba => JxlColorType::Rgba,
+        ColorType::Bgr => JxlColorType::Bgr,
+        ColorType::Bgra => JxlColorType::Bgra,
+    };
+
+    let Some(interleaved) = image((input.interleaved.0 as usize, input.interleaved.1 as usize), input.value) else {
+        return;
+    };
+    let mut channels = match color_type {
+        JxlColorType::Grayscale | JxlColorType::GrayscaleAlpha => vec![interleaved],
+        _ => match split_rgb_channels(&interleaved, width, height) {
+            Ok((r, g, b)) => vec![r, g, b],
+            Err(_) => return,
+        },
+    };
+    for &(w, h) in input.extra.iter().take(2) {
+        channels.extend(image((w as usize, h as usize), input.value));
+    }
+
+    let rgba = jxl_to_rgba8(&channels, color_type, width, height);
+    assert_eq!(rgba.len(), width * height * 4);
+});
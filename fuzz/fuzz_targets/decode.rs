@@ -0,0 +1,29 @@
+//! Arbitrary bytes through the viewer's whole decode path: header, frames,
+//! channel splitting and RGBA8 conversion
+//!
+//! Panics are not caught here, unlike in the app, so the fuzzer sees them.
+
+#![no_main]
+
+use jxl_ui::decoder::worker::{decode_jxl_bytes, DecodeOptions};
+use jxl_ui::decoder::{DecodeResult, DecodedFrame};
+use libfuzzer_sys::fuzz_target;
+
+/// Keeps huge declared sizes from turning into out-of-memory reports
+const PIXEL_LIMIT: usize = 4_000_000;
+
+fn check_frame(frame: &DecodedFrame) {
+    assert_eq!(frame.rgba_data.len(), frame.width as usize * frame.height as usize * 4);
+}
+
+fuzz_target!(|data: &[u8]| {
+    let options = DecodeOptions { pixel_limit: Some(PIXEL_LIMIT), ..DecodeOptions::default() };
+    match decode_jxl_bytes(data, &options) {
+        Ok(DecodeResult::SingleFrame { frame, .. }) => check_frame(&frame),
+        Ok(DecodeResult::Animation { frames, metadata }) => {
+            assert_eq!(frames.len(), metadata.frame_count);
+            frames.iter().for_each(check_frame);
+        }
+        Err(_) => {}
+    }
+});
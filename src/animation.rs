@@ -0,0 +1,326 @@
+//! Animation playback
+//!
+//! Frames are shown for their own durations, scaled by the playback speed,
+//! in forward, reverse or ping-pong order. Playback stops once the loop count
+//! stored in the file has been played; a loop count of zero loops forever.
+
+use crate::tiles::TiledImage;
+use crate::SharedRgba;
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum PlaybackMode {
+    Forward,
+    Reverse,
+    PingPong,
+}
+
+/// Animation playback state carried over when a tab is reloaded or restored
+#[derive(Clone, Copy)]
+pub struct PlaybackView {
+    pub frame: usize,
+    pub is_playing: bool,
+    pub speed: f32,
+    pub mode: PlaybackMode,
+}
+
+impl PlaybackView {
+    pub fn paused_at(frame: usize) -> Self {
+        Self {
+            frame,
+            is_playing: false,
+            speed: 1.0,
+            mode: PlaybackMode::Forward,
+        }
+    }
+}
+
+pub struct AnimationState {
+    pub frames: Vec<TiledImage>,
+    pub rgba: Vec<SharedRgba>,
+    pub durations: Vec<f64>, // Milliseconds
+    pub current_frame: usize,
+    // Time spent on the current frame, in animation milliseconds
+    frame_elapsed_ms: f64,
+    pub last_tick: Instant,
+    pub is_playing: bool,
+    pub speed: f32,
+    pub mode: PlaybackMode,
+    // Ping-pong is currently running backwards
+    ping_pong_reversed: bool,
+    pub loop_count: u32, // 0 = loop forever
+    pub loops_completed: u32,
+}
+
+impl AnimationState {
+    pub fn new(capacity: usize, loop_count: u32, is_playing: bool) -> Self {
+        Self {
+            frames: Vec::with_capacity(capacity),
+            rgba: Vec::with_capacity(capacity),
+            durations: Vec::with_capacity(capacity),
+            current_frame: 0,
+            frame_elapsed_ms: 0.0,
+            last_tick: Instant::now(),
+            is_playing,
+            speed: 1.0,
+            mode: PlaybackMode::Forward,
+            ping_pong_reversed: false,
+            loop_count,
+            loops_completed: 0,
+        }
+    }
+
+    /// Append a decoded frame shown for `duration_ms`
+    pub fn push(&mut self, frame: TiledImage, rgba: SharedRgba, duration_ms: f64) {
+        self.frames.push(frame);
+        self.rgba.push(rgba);
+        self.durations.push(duration_ms);
+    }
+
+    /// Jump to a frame, restarting its display time
+    pub fn seek(&mut self, frame: usize) {
+        if !self.frames.is_empty() {
+            self.current_frame = frame.min(self.frames.len() - 1);
+            self.frame_elapsed_ms = 0.0;
+        }
+    }
+
+    /// Play/pause; playing again after the final loop restarts from the beginning
+    pub fn toggle_playing(&mut self) {
+        if !self.is_playing && self.is_finished() {
+            self.loops_completed = 0;
+            self.ping_pong_reversed = false;
+            let start = if self.mode == PlaybackMode::Reverse { self.frames.len() } else { 0 };
+            self.seek(start);
+        }
+        self.is_playing = !self.is_playing;
+    }
+
+    /// All loops requested by the file have been played
+    pub fn is_finished(&self) -> bool {
+        self.loop_count > 0 && self.loops_completed >= self.loop_count
+    }
+
+    /// Advance the playback clock by wall-clock time `dt`
+    ///
+    /// Time is accumulated rather than measured per frame, so slow repaints
+    /// skip frames instead of stretching the animation. Returns the wall-clock
+    /// time until the next frame change.
+    pub fn tick(&mut self, dt: Duration) -> Option<Duration> {
+        if !self.is_playing || self.frames.is_empty() || self.total_ms() <= 0.0 {
+            return None;
+        }
+
+        self.frame_elapsed_ms += dt.as_secs_f64() * 1000.0 * self.speed as f64;
+
+        loop {
+            let duration = self.durations.get(self.current_frame).copied().unwrap_or(0.0);
+            if self.frame_elapsed_ms < duration {
+                let remaining_ms = (duration - self.frame_elapsed_ms) / self.speed as f64;
+                return Some(Duration::from_secs_f64(remaining_ms / 1000.0));
+            }
+
+            self.frame_elapsed_ms -= duration;
+            if !self.advance() {
+                self.is_playing = false;
+                self.frame_elapsed_ms = 0.0;
+                return None;
+            }
+        }
+    }
+
+    /// Step forwards or backwards by `delta` frames, wrapping around
+    pub fn step(&mut self, delta: isize) {
+        let len = self.frames.len() as isize;
+        if len > 0 {
+            self.seek((self.current_frame as isize + delta).rem_euclid(len) as usize);
+        }
+    }
+
+    /// Move to the next frame according to the playback mode
+    ///
+    /// Returns false, staying on the current frame, once the loop count is exhausted.
+    fn advance(&mut self) -> bool {
+        let last = self.frames.len().saturating_sub(1);
+        let (next, wrapped) = match self.mode {
+            PlaybackMode::Forward => {
+                if self.current_frame >= last { (0, true) } else { (self.current_frame + 1, false) }
+            }
+            PlaybackMode::Reverse => {
+                if self.current_frame == 0 { (last, true) } else { (self.current_frame - 1, false) }
+            }
+            PlaybackMode::PingPong => {
+                let mut wrapped = false;
+                if self.ping_pong_reversed && self.current_frame == 0 {
+                    self.ping_pong_reversed = false;
+                    wrapped = true;
+                } else if !self.ping_pong_reversed && self.current_frame >= last {
+                    self.ping_pong_reversed = true;
+                }
+                let next = if self.ping_pong_reversed {
+                    self.current_frame.saturating_sub(1)
+                } else {
+                    (self.current_frame + 1).min(last)
+                };
+                (next, wrapped)
+            }
+        };
+
+        if wrapped {
+            self.loops_completed += 1;
+            if self.is_finished() {
+                return false;
+            }
+        }
+
+        self.current_frame = next;
+        true
+    }
+
+    /// Start time of a frame in milliseconds
+    pub fn frame_start_ms(&self, frame: usize) -> f64 {
+        self.durations.iter().take(frame).sum()
+    }
+
+    pub fn total_ms(&self) -> f64 {
+        self.durations.iter().sum()
+    }
+
+    /// Frame shown at a timestamp in milliseconds
+    pub fn frame_at_ms(&self, ms: f64) -> usize {
+        let mut end = 0.0;
+        for (i, &d) in self.durations.iter().enumerate() {
+            end += d;
+            if ms < end {
+                return i;
+            }
+        }
+        self.frames.len().saturating_sub(1)
+    }
+
+    /// Number of steps until `frame` is shown, following the playback direction
+    pub fn frames_until(&self, frame: usize) -> usize {
+        let len = self.frames.len();
+        let forward = (frame + len - self.current_frame) % len.max(1);
+        let backward = (self.current_frame + len - frame) % len.max(1);
+        match self.mode {
+            PlaybackMode::Forward => forward,
+            PlaybackMode::Reverse => backward,
+            PlaybackMode::PingPong => forward.min(backward),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    fn animation(durations: &[f64], loop_count: u32) -> AnimationState {
+        let mut anim = AnimationState::new(durations.len(), loop_count, true);
+        for &duration in durations {
+            anim.push(TiledImage::new("test", Arc::new(vec![0; 4]), 1, 1, 16384), Arc::new(vec![0; 4]), duration);
+        }
+        anim
+    }
+
+    // Millisecond values used here are exact binary fractions of a second
+    fn after(ms: f64) -> Duration {
+        Duration::from_secs_f64(ms / 1000.0)
+    }
+
+    fn ms(next_change: Option<Duration>) -> Option<f64> {
+        next_change.map(|d| d.as_secs_f64() * 1000.0)
+    }
+
+    #[test]
+    fn test_stops_after_loop_count() {
+        let mut anim = animation(&[125.0, 125.0], 2);
+        assert_eq!(ms(anim.tick(after(187.5))), Some(62.5));
+        assert_eq!(anim.current_frame, 1);
+        assert_eq!(ms(anim.tick(after(62.5))), Some(125.0));
+        assert_eq!((anim.current_frame, anim.loops_completed), (0, 1));
+
+        // The second loop ends on the last frame and pauses there
+        assert_eq!(anim.tick(after(1000.0)), None);
+        assert_eq!((anim.current_frame, anim.loops_completed), (1, 2));
+        assert!(!anim.is_playing && anim.is_finished());
+
+        // Playing again restarts from the beginning
+        anim.toggle_playing();
+        assert!(anim.is_playing && !anim.is_finished());
+        assert_eq!(anim.current_frame, 0);
+    }
+
+    #[test]
+    fn test_zero_duration_frames() {
+        let mut anim = animation(&[125.0, 0.0, 0.0, 125.0], 0);
+        assert_eq!(ms(anim.tick(after(125.0))), Some(125.0));
+        assert_eq!(anim.current_frame, 3);
+
+        // An animation without any duration never advances
+        let mut anim = animation(&[0.0, 0.0], 0);
+        assert_eq!(anim.tick(after(1000.0)), None);
+        assert_eq!(anim.current_frame, 0);
+    }
+
+    #[test]
+    fn test_slow_repaints_skip_frames() {
+        let mut anim = animation(&[125.0; 4], 0);
+        assert_eq!(ms(anim.tick(after(312.5))), Some(62.5));
+        assert_eq!(anim.current_frame, 2);
+
+        // Time left over on frame 2 carries into the next loop
+        assert_eq!(ms(anim.tick(after(250.0))), Some(62.5));
+        assert_eq!((anim.current_frame, anim.loops_completed), (0, 1));
+        assert!(anim.is_playing);
+    }
+
+    #[test]
+    fn test_speed() {
+        let mut anim = animation(&[125.0, 125.0], 0);
+        anim.speed = 2.0;
+        assert_eq!(ms(anim.tick(after(31.25))), Some(31.25));
+        assert_eq!(ms(anim.tick(after(31.25))), Some(62.5));
+        assert_eq!(anim.current_frame, 1);
+
+        let mut anim = animation(&[125.0, 125.0], 0);
+        anim.speed = 0.5;
+        assert_eq!(ms(anim.tick(after(125.0))), Some(125.0));
+        assert_eq!(anim.current_frame, 0);
+    }
+
+    #[test]
+    fn test_ping_pong() {
+        let mut anim = animation(&[125.0; 3], 2);
+        anim.mode = PlaybackMode::PingPong;
+        let shown: Vec<usize> = (0..8)
+            .map(|_| {
+                anim.tick(after(125.0));
+                anim.current_frame
+            })
+            .collect();
+        assert_eq!(shown, [1, 2, 1, 0, 1, 2, 1, 0]);
+        assert_eq!(anim.loops_completed, 1);
+
+        // Returning to the first frame completes the second loop
+        assert_eq!(anim.tick(after(125.0)), None);
+        assert_eq!((anim.current_frame, anim.loops_completed), (0, 2));
+        assert!(!anim.is_playing);
+    }
+
+    #[test]
+    fn test_frames_until() {
+        let mut anim = animation(&[125.0; 4], 0);
+        anim.seek(1);
+        assert_eq!((anim.frames_until(0), anim.frames_until(3)), (3, 2));
+        anim.mode = PlaybackMode::Reverse;
+        assert_eq!((anim.frames_until(0), anim.frames_until(3)), (1, 2));
+        anim.mode = PlaybackMode::PingPong;
+        assert_eq!((anim.frames_until(0), anim.frames_until(2)), (1, 1));
+
+        anim.step(-2);
+        assert_eq!(anim.current_frame, 3);
+        assert_eq!(anim.frame_at_ms(anim.frame_start_ms(2)), 2);
+    }
+}
@@ -0,0 +1,378 @@
+//! System clipboard access
+//!
+//! The clipboard is kept open for the life of the app: on X11 the copied data
+//! is served by this process and disappears once the handle is dropped.
+//!
+//! Pasting goes through the [`Clipboard`] trait so the decision of what to
+//! open can be tested without a display server.
+
+use anyhow::Result;
+use jxl_ui::decoder::container::{detect_kind, FileKind};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+/// Largest pasted or downloaded file
+pub const MAX_DATA_BYTES: u64 = 512 * 1024 * 1024;
+
+/// How long a paste waits for the copying program to hand over JXL data
+const PASTE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Longest a URL download may take, including the body
+pub const DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Temporary files of earlier runs are deleted once they are this old
+const STALE_TEMP_AGE: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+pub trait Clipboard {
+    fn set_text(&mut self, text: String) -> Result<()>;
+    /// Put straight-alpha RGBA8 pixels on the clipboard
+    fn set_image(&mut self, width: u32, height: u32, rgba: Vec<u8>) -> Result<()>;
+    fn get_text(&mut self) -> Option<String>;
+    /// Files copied in a file manager
+    fn get_files(&mut self) -> Vec<PathBuf>;
+    /// Raw JPEG XL file contents, where the platform can offer them
+    fn get_jxl(&mut self) -> Option<Vec<u8>>;
+}
+
+pub struct SystemClipboard(arboard::Clipboard);
+
+impl SystemClipboard {
+    pub fn new() -> Result<Self> {
+        Ok(Self(arboard::Clipboard::new()?))
+    }
+}
+
+impl Clipboard for SystemClipboard {
+    fn set_text(&mut self, text: String) -> Result<()> {
+        self.0.set_text(text)?;
+        Ok(())
+    }
+
+    fn set_image(&mut self, width: u32, height: u32, rgba: Vec<u8>) -> Result<()> {
+        self.0.set_image(arboard::ImageData {
+            width: width as usize,
+            height: height as usize,
+            bytes: rgba.into(),
+        })?;
+        Ok(())
+    }
+
+    fn get_text(&mut self) -> Option<String> {
+        self.0.get_text().ok()
+    }
+
+    fn get_files(&mut self) -> Vec<PathBuf> {
+        self.0.get().file_list().unwrap_or_default()
+    }
+
+    // arboard only reads text, images and file lists; other MIME types need
+    // the Wayland protocol or X11 selections directly
+    #[cfg(all(unix, not(target_os = "macos")))]
+    fn get_jxl(&mut self) -> Option<Vec<u8>> {
+        if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+            return wayland_jxl();
+        }
+        std::env::var_os("DISPLAY")?;
+        x11_jxl()
+    }
+
+    #[cfg(not(all(unix, not(target_os = "macos"))))]
+    fn get_jxl(&mut self) -> Option<Vec<u8>> {
+        None
+    }
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn wayland_jxl() -> Option<Vec<u8>> {
+    use std::sync::mpsc::channel;
+    use wl_clipboard_rs::paste::{get_contents, ClipboardType, Error, MimeType, Seat};
+
+    match get_contents(ClipboardType::Regular, Seat::Unspecified, MimeType::Specific("image/jxl")) {
+        Ok((pipe, _)) => {
+            // The copying program writes the pipe and may never close it, so
+            // read on another thread; a stuck reader ends when that program exits
+            let (tx, rx) = channel();
+            std::thread::spawn(move || {
+                let _ = tx.send(read_limited(pipe, MAX_DATA_BYTES));
+            });
+            match rx.recv_timeout(PASTE_TIMEOUT) {
+                Ok(Ok(bytes)) => Some(bytes),
+                Ok(Err(e)) => {
+                    log::warn!("Failed to read pasted JXL data: {}", e);
+                    None
+                }
+                Err(_) => {
+                    log::warn!("Timed out reading pasted JXL data");
+                    None
+                }
+            }
+        }
+        Err(Error::NoSeats | Error::ClipboardEmpty | Error::NoMimeType) => None,
+        Err(e) => {
+            log::warn!("Failed to read the Wayland clipboard: {}", e);
+            None
+        }
+    }
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn x11_jxl() -> Option<Vec<u8>> {
+    let clipboard = x11_clipboard::Clipboard::new()
+        .map_err(|e| log::warn!("Failed to open the X11 clipboard: {}", e))
+        .ok()?;
+    let target = clipboard.getter.get_atom("image/jxl").ok()?;
+    let atoms = &clipboard.getter.atoms;
+    match clipboard.load(atoms.clipboard, target, atoms.property, PASTE_TIMEOUT) {
+        Ok(bytes) if bytes.len() as u64 > MAX_DATA_BYTES => {
+            log::warn!("Ignoring {} bytes of pasted JXL data, above the size limit", bytes.len());
+            None
+        }
+        Ok(bytes) => (!bytes.is_empty()).then_some(bytes),
+        // Also the result when the owner does not offer `image/jxl`
+        Err(e) => {
+            log::debug!("No JXL data on the X11 clipboard: {}", e);
+            None
+        }
+    }
+}
+
+/// Read all of `reader`, failing once it holds more than `limit` bytes
+pub fn read_limited(reader: impl Read, limit: u64) -> Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    reader.take(limit + 1).read_to_end(&mut bytes)?;
+    if bytes.len() as u64 > limit {
+        anyhow::bail!("Larger than {} MB", limit / (1024 * 1024));
+    }
+    Ok(bytes)
+}
+
+/// What a paste should open
+#[derive(Debug, PartialEq)]
+pub enum PasteTarget {
+    File(PathBuf),
+    Url(String),
+    /// Contents of a JXL file, not yet saved anywhere
+    Bytes(Vec<u8>),
+}
+
+/// Pick what to open from the clipboard: JXL data first, then copied files,
+/// then a path or URL in the text
+pub fn paste_target(clipboard: &mut dyn Clipboard) -> Option<PasteTarget> {
+    if let Some(bytes) = clipboard.get_jxl().filter(|b| detect_kind(b) != FileKind::Unknown) {
+        return Some(PasteTarget::Bytes(bytes));
+    }
+    if let Some(path) = clipboard.get_files().into_iter().find(|p| p.is_file()) {
+        return Some(PasteTarget::File(path));
+    }
+    parse_text(&clipboard.get_text()?)
+}
+
+/// First line of `text` naming an existing file or an http(s) URL
+///
+/// File managers often copy a list of `file://` URIs, sometimes behind a
+/// header line such as GNOME's `x-special/nautilus-clipboard`.
+fn parse_text(text: &str) -> Option<PasteTarget> {
+    text.lines().find_map(|line| {
+        let line = line.trim().trim_matches(|c| c == '"' || c == '\'');
+        if line.starts_with("http://") || line.starts_with("https://") {
+            return Some(PasteTarget::Url(line.to_string()));
+        }
+        let path = if let Some(uri) = line.strip_prefix("file://") {
+            PathBuf::from(percent_decode(uri.strip_prefix("localhost").unwrap_or(uri)))
+        } else if let Some(rest) = line.strip_prefix("~/") {
+            dirs::home_dir()?.join(rest)
+        } else {
+            PathBuf::from(line)
+        };
+        (!line.is_empty() && path.is_file()).then_some(PasteTarget::File(path))
+    })
+}
+
+/// Decode `%XX` escapes, as found in URIs
+pub fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes.get(i + 1..i + 3)
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                out.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                out.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// File name for a download, from the last segment of the URL path
+pub fn url_file_name(url: &str) -> String {
+    let path = url.split(['?', '#']).next().unwrap_or(url);
+    let path = path.split_once("://").map_or(path, |(_, rest)| rest);
+    let name = path.split_once('/').and_then(|(_, p)| p.rsplit('/').next()).unwrap_or("");
+    match percent_decode(name) {
+        name if name.is_empty() => "download.jxl".to_string(),
+        name => name,
+    }
+}
+
+/// Write pasted or downloaded data to a new file in the temporary directory,
+/// so it can be opened like any other file
+///
+/// Files go in a directory of their own under this process's directory, which
+/// [`remove_temp_files`] deletes on exit.
+pub fn save_temp(name: &str, bytes: &[u8]) -> Result<PathBuf> {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+    let dir = process_temp_dir().join(COUNT.fetch_add(1, Ordering::Relaxed).to_string());
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join(Path::new(name).file_name().unwrap_or("pasted.jxl".as_ref()));
+    std::fs::write(&path, bytes)?;
+    Ok(path)
+}
+
+/// Directory holding the files this process pasted or downloaded
+fn process_temp_dir() -> PathBuf {
+    std::env::temp_dir().join("jxl-ui").join(std::process::id().to_string())
+}
+
+/// Delete the files this process pasted or downloaded
+pub fn remove_temp_files() {
+    match std::fs::remove_dir_all(process_temp_dir()) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+            log::warn!("Failed to remove temporary files: {}", e);
+        }
+        _ => {}
+    }
+}
+
+/// Delete temporary files left behind by runs that did not exit cleanly
+pub fn remove_stale_temp_files() {
+    let Ok(entries) = std::fs::read_dir(std::env::temp_dir().join("jxl-ui")) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let stale = entry.metadata()
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|modified| modified.elapsed().ok())
+            .is_some_and(|age| age > STALE_TEMP_AGE);
+        if stale {
+            log::info!("Removing old temporary files in {:?}", entry.path());
+            std::fs::remove_dir_all(entry.path()).ok();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct FakeClipboard {
+        text: Option<String>,
+        files: Vec<PathBuf>,
+        jxl: Option<Vec<u8>>,
+    }
+
+    impl Clipboard for FakeClipboard {
+        fn set_text(&mut self, text: String) -> Result<()> {
+            self.text = Some(text);
+            Ok(())
+        }
+
+        fn set_image(&mut self, _width: u32, _height: u32, _rgba: Vec<u8>) -> Result<()> {
+            Ok(())
+        }
+
+        fn get_text(&mut self) -> Option<String> {
+            self.text.clone()
+        }
+
+        fn get_files(&mut self) -> Vec<PathBuf> {
+            self.files.clone()
+        }
+
+        fn get_jxl(&mut self) -> Option<Vec<u8>> {
+            self.jxl.clone()
+        }
+    }
+
+    fn manifest() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("Cargo.toml")
+    }
+
+    #[test]
+    fn test_paste_priority() {
+        let mut clipboard = FakeClipboard {
+            text: Some("https://example.com/a.jxl".to_string()),
+            files: vec![manifest()],
+            jxl: Some(vec![0xFF, 0x0A, 1, 2]),
+        };
+        assert_eq!(paste_target(&mut clipboard), Some(PasteTarget::Bytes(vec![0xFF, 0x0A, 1, 2])));
+
+        // Data under the JXL type that is not JXL is ignored
+        clipboard.jxl = Some(b"GIF89a".to_vec());
+        assert_eq!(paste_target(&mut clipboard), Some(PasteTarget::File(manifest())));
+
+        clipboard.files.clear();
+        assert_eq!(paste_target(&mut clipboard), Some(PasteTarget::Url("https://example.com/a.jxl".to_string())));
+
+        clipboard.text = Some("just some words".to_string());
+        assert_eq!(paste_target(&mut clipboard), None);
+        assert_eq!(paste_target(&mut FakeClipboard::default()), None);
+    }
+
+    #[test]
+    fn test_parse_text() {
+        let path = manifest();
+        let quoted = format!("  \"{}\"\n", path.display());
+        assert_eq!(parse_text(&quoted), Some(PasteTarget::File(path.clone())));
+
+        let uri = format!("file://{}", path.display()).replace(' ', "%20");
+        let nautilus = format!("x-special/nautilus-clipboard\ncopy\n{}\n", uri);
+        assert_eq!(parse_text(&nautilus), Some(PasteTarget::File(path.clone())));
+        let localhost = format!("file://localhost{}", path.display()).replace(' ', "%20");
+        assert_eq!(parse_text(&localhost), Some(PasteTarget::File(path)));
+
+        assert_eq!(parse_text("/no/such/file.jxl"), None);
+        assert_eq!(parse_text(""), None);
+    }
+
+    #[test]
+    fn test_read_limited() {
+        assert_eq!(read_limited(&[1u8, 2, 3][..], 3).unwrap(), [1, 2, 3]);
+        assert!(read_limited(&[1u8, 2, 3, 4][..], 3).is_err());
+        // Readers that never end are cut off at the limit
+        assert!(read_limited(std::io::repeat(0), 1024).is_err());
+    }
+
+    #[test]
+    fn test_save_temp() {
+        // Files with the same name do not overwrite each other
+        let a = save_temp("pasted.jxl", b"a").unwrap();
+        let b = save_temp("pasted.jxl", b"b").unwrap();
+        assert_ne!(a, b);
+        assert_eq!(a.file_name(), b.file_name());
+        assert!(a.starts_with(process_temp_dir()));
+        assert_eq!(std::fs::read(&a).unwrap(), b"a");
+
+        remove_temp_files();
+        assert!(!a.exists() && !b.exists());
+    }
+
+    #[test]
+    fn test_url_file_name() {
+        assert_eq!(url_file_name("https://example.com/img/cat%20photo.jxl?w=2#top"), "cat photo.jxl");
+        assert_eq!(url_file_name("https://example.com/"), "download.jxl");
+        assert_eq!(url_file_name("https://example.com"), "download.jxl");
+        assert_eq!(percent_decode("100%25 %zz%4"), "100% %zz%4");
+    }
+}
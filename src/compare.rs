@@ -0,0 +1,325 @@
+//! Image comparison: difference images and objective quality metrics
+//!
+//! All functions operate on interleaved RGBA8 buffers as produced by the decoder.
+//! Alpha is ignored; only the color channels are compared.
+
+use anyhow::Result;
+
+/// How a difference image is visualised
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DiffMode {
+    /// Per-channel absolute difference, multiplied by the amplification factor
+    Absolute,
+    /// Signed luma difference: red where the second image is brighter, blue where darker
+    Heatmap,
+}
+
+/// Objective quality metrics between a reference and a distorted image
+#[derive(Clone, Copy, Debug)]
+pub struct Metrics {
+    /// Peak signal-to-noise ratio over RGB in dB (infinite for identical images)
+    pub psnr: f64,
+    /// Mean structural similarity of the luma channel (1.0 = identical)
+    pub ssim: f64,
+    /// Maximum perceptual distance in XYB space (Butteraugli-style, not libjxl's Butteraugli)
+    pub xyb_max: f64,
+    /// 3-norm of the perceptual distance in XYB space
+    pub xyb_pnorm: f64,
+}
+
+/// Fail with a readable message if two images don't have the same dimensions
+pub fn check_dimensions(a: (u32, u32), b: (u32, u32)) -> Result<()> {
+    if a != b {
+        anyhow::bail!(
+            "Images must have the same dimensions ({}×{} vs {}×{})",
+            a.0,
+            a.1,
+            b.0,
+            b.1
+        );
+    }
+    Ok(())
+}
+
+/// Compute all metrics between two RGBA8 images of the same size
+pub fn compute_metrics(a: &[u8], b: &[u8], width: usize, height: usize) -> Result<Metrics> {
+    if a.len() != width * height * 4 || b.len() != width * height * 4 {
+        anyhow::bail!("Pixel buffers don't match the image dimensions");
+    }
+
+    let (xyb_max, xyb_pnorm) = xyb_distance(a, b, width, height);
+
+    Ok(Metrics {
+        psnr: psnr(a, b),
+        ssim: ssim(a, b, width, height),
+        xyb_max,
+        xyb_pnorm,
+    })
+}
+
+/// Render a difference image between two RGBA8 images of the same size
+pub fn difference_image(a: &[u8], b: &[u8], mode: DiffMode, amplification: f32) -> Vec<u8> {
+    let mut out = vec![0u8; a.len()];
+
+    for ((dst, pa), pb) in out
+        .chunks_exact_mut(4)
+        .zip(a.chunks_exact(4))
+        .zip(b.chunks_exact(4))
+    {
+        match mode {
+            DiffMode::Absolute => {
+                for c in 0..3 {
+                    let diff = (pa[c] as f32 - pb[c] as f32).abs() * amplification;
+                    dst[c] = diff.min(255.0) as u8;
+                }
+            }
+            DiffMode::Heatmap => {
+                let diff = (luma(pb) - luma(pa)) * amplification / 255.0;
+                let t = diff.clamp(-1.0, 1.0);
+                // Diverging map: black at zero, red for positive, blue for negative
+                if t >= 0.0 {
+                    dst[0] = (t * 255.0) as u8;
+                    dst[1] = (t * 64.0) as u8;
+                } else {
+                    dst[1] = (-t * 64.0) as u8;
+                    dst[2] = (-t * 255.0) as u8;
+                }
+            }
+        }
+        dst[3] = 255;
+    }
+
+    out
+}
+
+/// BT.601 luma of an RGBA8 pixel in [0, 255]
+#[inline]
+fn luma(px: &[u8]) -> f32 {
+    0.299 * px[0] as f32 + 0.587 * px[1] as f32 + 0.114 * px[2] as f32
+}
+
+/// Peak signal-to-noise ratio over the RGB channels
+fn psnr(a: &[u8], b: &[u8]) -> f64 {
+    let mut sum_sq = 0.0f64;
+    let mut count = 0usize;
+
+    for (pa, pb) in a.chunks_exact(4).zip(b.chunks_exact(4)) {
+        for c in 0..3 {
+            let d = pa[c] as f64 - pb[c] as f64;
+            sum_sq += d * d;
+        }
+        count += 3;
+    }
+
+    if count == 0 || sum_sq == 0.0 {
+        return f64::INFINITY;
+    }
+
+    let mse = sum_sq / count as f64;
+    10.0 * (255.0 * 255.0 / mse).log10()
+}
+
+/// Mean SSIM over the luma channel with an 11-tap Gaussian window (sigma 1.5)
+fn ssim(a: &[u8], b: &[u8], width: usize, height: usize) -> f64 {
+    const C1: f32 = (0.01 * 255.0) * (0.01 * 255.0);
+    const C2: f32 = (0.03 * 255.0) * (0.03 * 255.0);
+
+    let la: Vec<f32> = a.chunks_exact(4).map(luma).collect();
+    let lb: Vec<f32> = b.chunks_exact(4).map(luma).collect();
+    let aa: Vec<f32> = la.iter().map(|v| v * v).collect();
+    let bb: Vec<f32> = lb.iter().map(|v| v * v).collect();
+    let ab: Vec<f32> = la.iter().zip(&lb).map(|(x, y)| x * y).collect();
+
+    let mu_a = gaussian_blur(&la, width, height, 1.5);
+    let mu_b = gaussian_blur(&lb, width, height, 1.5);
+    let s_aa = gaussian_blur(&aa, width, height, 1.5);
+    let s_bb = gaussian_blur(&bb, width, height, 1.5);
+    let s_ab = gaussian_blur(&ab, width, height, 1.5);
+
+    let mut sum = 0.0f64;
+    for i in 0..la.len() {
+        let (ma, mb) = (mu_a[i], mu_b[i]);
+        let var_a = s_aa[i] - ma * ma;
+        let var_b = s_bb[i] - mb * mb;
+        let cov = s_ab[i] - ma * mb;
+
+        let num = (2.0 * ma * mb + C1) * (2.0 * cov + C2);
+        let den = (ma * ma + mb * mb + C1) * (var_a + var_b + C2);
+        sum += (num / den) as f64;
+    }
+
+    if la.is_empty() {
+        1.0
+    } else {
+        sum / la.len() as f64
+    }
+}
+
+/// Perceptual distance in XYB space, returning (max, 3-norm)
+///
+/// This follows the spirit of Butteraugli (opsin-space differences, blurred,
+/// reported as max and p-norm) but is a much simpler model. Its values are
+/// useful for relative comparisons only and do not match libjxl's Butteraugli.
+fn xyb_distance(a: &[u8], b: &[u8], width: usize, height: usize) -> (f64, f64) {
+    const X_WEIGHT: f32 = 12.0;
+    const B_WEIGHT: f32 = 0.5;
+
+    let xa = to_xyb(a);
+    let xb = to_xyb(b);
+
+    let mut dx = Vec::with_capacity(xa.len());
+    let mut dy = Vec::with_capacity(xa.len());
+    let mut db = Vec::with_capacity(xa.len());
+    for (pa, pb) in xa.iter().zip(&xb) {
+        dx.push((pa[0] - pb[0]).abs());
+        dy.push((pa[1] - pb[1]).abs());
+        db.push((pa[2] - pb[2]).abs());
+    }
+
+    // Blur the error to approximate spatial pooling of visible differences
+    let dx = gaussian_blur(&dx, width, height, 1.2);
+    let dy = gaussian_blur(&dy, width, height, 1.2);
+    let db = gaussian_blur(&db, width, height, 1.2);
+
+    let mut max = 0.0f64;
+    let mut sum_cubed = 0.0f64;
+    for i in 0..dx.len() {
+        let d = ((X_WEIGHT * dx[i]).powi(2) + dy[i].powi(2) + (B_WEIGHT * db[i]).powi(2)).sqrt() as f64
+            * 10.0;
+        max = max.max(d);
+        sum_cubed += d * d * d;
+    }
+
+    let pnorm = if dx.is_empty() {
+        0.0
+    } else {
+        (sum_cubed / dx.len() as f64).cbrt()
+    };
+
+    (max, pnorm)
+}
+
+/// Convert RGBA8 (sRGB) pixels to XYB using the JPEG XL opsin absorbance matrix
+fn to_xyb(rgba: &[u8]) -> Vec<[f32; 3]> {
+    const BIAS: f32 = 0.003_793_073_3;
+    let bias_cbrt = BIAS.cbrt();
+
+    rgba.chunks_exact(4)
+        .map(|px| {
+            let r = srgb_to_linear(px[0]);
+            let g = srgb_to_linear(px[1]);
+            let b = srgb_to_linear(px[2]);
+
+            let l = 0.30 * r + 0.622 * g + 0.078 * b + BIAS;
+            let m = 0.23 * r + 0.692 * g + 0.078 * b + BIAS;
+            let s = 0.243_422_69 * r + 0.204_767_45 * g + 0.551_809_87 * b + BIAS;
+
+            let l = l.cbrt() - bias_cbrt;
+            let m = m.cbrt() - bias_cbrt;
+            let s = s.cbrt() - bias_cbrt;
+
+            [(l - m) * 0.5, (l + m) * 0.5, s]
+        })
+        .collect()
+}
+
+/// sRGB transfer function to linear light
+#[inline]
+fn srgb_to_linear(v: u8) -> f32 {
+    let v = v as f32 / 255.0;
+    if v <= 0.04045 {
+        v / 12.92
+    } else {
+        ((v + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Separable Gaussian blur with clamp-to-edge borders
+fn gaussian_blur(plane: &[f32], width: usize, height: usize, sigma: f32) -> Vec<f32> {
+    let radius = (sigma * 3.0).ceil() as isize;
+    let mut kernel: Vec<f32> = (-radius..=radius)
+        .map(|i| (-(i * i) as f32 / (2.0 * sigma * sigma)).exp())
+        .collect();
+    let total: f32 = kernel.iter().sum();
+    for k in &mut kernel {
+        *k /= total;
+    }
+
+    let mut tmp = vec![0.0f32; plane.len()];
+    for y in 0..height {
+        let row = &plane[y * width..(y + 1) * width];
+        for x in 0..width {
+            let mut acc = 0.0;
+            for (k, weight) in kernel.iter().enumerate() {
+                let sx = (x as isize + k as isize - radius).clamp(0, width as isize - 1) as usize;
+                acc += row[sx] * weight;
+            }
+            tmp[y * width + x] = acc;
+        }
+    }
+
+    let mut out = vec![0.0f32; plane.len()];
+    for y in 0..height {
+        for x in 0..width {
+            let mut acc = 0.0;
+            for (k, weight) in kernel.iter().enumerate() {
+                let sy = (y as isize + k as isize - radius).clamp(0, height as isize - 1) as usize;
+                acc += tmp[sy * width + x] * weight;
+            }
+            out[y * width + x] = acc;
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn solid(width: usize, height: usize, value: u8) -> Vec<u8> {
+        let mut rgba = vec![value; width * height * 4];
+        for px in rgba.chunks_exact_mut(4) {
+            px[3] = 255;
+        }
+        rgba
+    }
+
+    #[test]
+    fn test_identical_images() {
+        let img = solid(8, 8, 100);
+        let metrics = compute_metrics(&img, &img, 8, 8).unwrap();
+
+        assert!(metrics.psnr.is_infinite());
+        assert!((metrics.ssim - 1.0).abs() < 1e-6);
+        assert_eq!(metrics.xyb_max, 0.0);
+    }
+
+    #[test]
+    fn test_known_psnr() {
+        // Uniform error of 1 level on every channel: PSNR = 20 * log10(255) ≈ 48.13 dB
+        let a = solid(4, 4, 100);
+        let b = solid(4, 4, 101);
+        let metrics = compute_metrics(&a, &b, 4, 4).unwrap();
+
+        assert!((metrics.psnr - 48.13).abs() < 0.01);
+        assert!(metrics.xyb_max > 0.0);
+    }
+
+    #[test]
+    fn test_dimension_mismatch() {
+        assert!(check_dimensions((4, 4), (4, 4)).is_ok());
+
+        let err = check_dimensions((4, 4), (8, 4)).unwrap_err();
+        assert!(err.to_string().contains("4×4 vs 8×4"));
+    }
+
+    #[test]
+    fn test_difference_amplification() {
+        let a = solid(1, 1, 100);
+        let b = solid(1, 1, 104);
+        let diff = difference_image(&a, &b, DiffMode::Absolute, 10.0);
+
+        assert_eq!(&diff[..4], &[40, 40, 40, 255]);
+    }
+}
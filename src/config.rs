@@ -0,0 +1,210 @@
+//! Settings and session state, saved as TOML in the user's config directory
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// User preferences
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// Textures and decoded pixels shared by all tabs, in megabytes
+    pub memory_budget_mb: usize,
+    /// Largest image to decode, in megapixels; 0 disables the limit
+    pub pixel_limit_mp: usize,
+    /// Start animations playing as soon as they are loaded
+    pub autoplay: bool,
+    /// Threads for converting decoded pixels to RGBA; 0 uses one per core
+    pub decoder_threads: usize,
+    /// Seconds each image is shown in a slideshow
+    pub slideshow_interval_secs: f32,
+    pub show_info: bool,
+    pub show_structure: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            memory_budget_mb: 1024,
+            pixel_limit_mp: 256,
+            autoplay: true,
+            decoder_threads: 0,
+            slideshow_interval_secs: 5.0,
+            show_info: false,
+            show_structure: false,
+        }
+    }
+}
+
+impl Settings {
+    pub fn pixel_limit(&self) -> Option<usize> {
+        (self.pixel_limit_mp > 0).then_some(self.pixel_limit_mp * 1_000_000)
+    }
+
+    pub fn decoder_threads(&self) -> Option<usize> {
+        (self.decoder_threads > 0).then_some(self.decoder_threads)
+    }
+
+    pub fn slideshow_interval(&self) -> Duration {
+        Duration::try_from_secs_f32(self.slideshow_interval_secs)
+            .unwrap_or(Duration::from_secs(5))
+            .max(Duration::from_millis(500))
+    }
+}
+
+/// An open tab, restored on the next launch
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SessionTab {
+    pub path: PathBuf,
+    /// Animation frame to show, for animations that were paused
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub frame: Option<usize>,
+    /// EXIF orientation value (2-8) the view was rotated or flipped to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub orientation: Option<u8>,
+    /// Decoded without applying the header orientation
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub raw_orientation: bool,
+}
+
+fn is_false(value: &bool) -> bool {
+    !value
+}
+
+/// Tabs open when the app was last closed
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Session {
+    pub active_tab: usize,
+    pub tabs: Vec<SessionTab>,
+}
+
+/// Length of the recent files list
+pub const MAX_RECENT_FILES: usize = 10;
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub settings: Settings,
+    pub session: Session,
+    /// Most recently opened first
+    pub recent_files: Vec<PathBuf>,
+}
+
+/// Move `path` to the front of a most-recently-used list
+pub fn push_recent(recent: &mut Vec<PathBuf>, path: PathBuf) {
+    recent.retain(|p| *p != path);
+    recent.insert(0, path);
+    recent.truncate(MAX_RECENT_FILES);
+}
+
+impl Config {
+    /// Location of the config file, e.g. `~/.config/jxl-ui/config.toml`
+    pub fn path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("jxl-ui").join("config.toml"))
+    }
+
+    /// Load the saved config, falling back to defaults if it is missing or invalid
+    pub fn load() -> Self {
+        let Some(path) = Self::path() else {
+            return Self::default();
+        };
+        match std::fs::read_to_string(&path) {
+            Ok(text) => Self::from_toml(&text).unwrap_or_else(|e| {
+                log::warn!("Ignoring invalid config {:?}: {}", path, e);
+                Self::default()
+            }),
+            Err(_) => Self::default(),
+        }
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = Self::path().ok_or_else(|| anyhow::anyhow!("No config directory"))?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&path, self.to_toml()?)?;
+        Ok(())
+    }
+
+    pub fn from_toml(text: &str) -> Result<Self> {
+        Ok(toml::from_str(text)?)
+    }
+
+    pub fn to_toml(&self) -> Result<String> {
+        Ok(toml::to_string_pretty(self)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let config = Config {
+            settings: Settings {
+                pixel_limit_mp: 0,
+                autoplay: false,
+                show_info: true,
+                ..Settings::default()
+            },
+            session: Session {
+                active_tab: 1,
+                tabs: vec![
+                    SessionTab {
+                        path: PathBuf::from("/tmp/a.jxl"),
+                        frame: None,
+                        orientation: Some(6),
+                        raw_orientation: true,
+                    },
+                    SessionTab {
+                        path: PathBuf::from("/tmp/anim.jxl"),
+                        frame: Some(7),
+                        orientation: None,
+                        raw_orientation: false,
+                    },
+                ],
+            },
+            recent_files: vec![PathBuf::from("/tmp/anim.jxl")],
+        };
+
+        let text = config.to_toml().unwrap();
+        assert_eq!(Config::from_toml(&text).unwrap(), config);
+        assert_eq!(config.settings.pixel_limit(), None);
+    }
+
+    #[test]
+    fn test_partial_config() {
+        // Keys missing from older config files take their defaults
+        let config = Config::from_toml("[settings]\nautoplay = false\n").unwrap();
+        assert!(!config.settings.autoplay);
+        assert_eq!(config.settings.memory_budget_mb, 1024);
+        assert_eq!(config.settings.pixel_limit(), Some(256_000_000));
+        assert!(config.session.tabs.is_empty());
+
+        // Tabs saved before the view state was stored open unrotated
+        let config = Config::from_toml("[[session.tabs]]\npath = \"/tmp/a.jxl\"\n").unwrap();
+        assert_eq!(config.session.tabs[0].orientation, None);
+        assert!(!config.session.tabs[0].raw_orientation);
+
+        assert!(Config::from_toml("[settings]\nautoplay = 3\n").is_err());
+    }
+
+    #[test]
+    fn test_push_recent() {
+        let mut recent = Vec::new();
+        for i in 0..12 {
+            push_recent(&mut recent, PathBuf::from(format!("{}.jxl", i)));
+        }
+        assert_eq!(recent.len(), MAX_RECENT_FILES);
+        assert_eq!(recent[0], PathBuf::from("11.jxl"));
+
+        // Reopening moves a file to the front without duplicating it
+        push_recent(&mut recent, PathBuf::from("5.jxl"));
+        assert_eq!(recent.len(), MAX_RECENT_FILES);
+        assert_eq!(recent[0], PathBuf::from("5.jxl"));
+        assert_eq!(recent.iter().filter(|p| p.as_os_str() == "5.jxl").count(), 1);
+    }
+}
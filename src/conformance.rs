@@ -0,0 +1,346 @@
+//! Runner for the JPEG XL conformance suite (github.com/libjxl/conformance)
+//!
+//! Expects a local checkout with one directory per test case:
+//!
+//! ```text
+//! testcases/<name>/input.jxl
+//! testcases/<name>/reference_image.npy   float32, frames × height × width × channels
+//! testcases/<name>/test.json             "rms_error" and "peak_error" tolerances
+//! ```
+//!
+//! Decoded samples are compared as-is against the reference, with no colour
+//! management, so cases whose reference is in a different colour space than
+//! the codestream fail rather than being skipped.
+
+use crate::decoder::worker::{catch_panic, decode_float_frames, DecodeOptions};
+use crate::decoder::DecodedFloatFrame;
+use anyhow::{bail, Result};
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+/// Tolerances used when a case's `test.json` does not set them
+pub const DEFAULT_RMS_ERROR: f64 = 0.001;
+pub const DEFAULT_PEAK_ERROR: f64 = 0.004;
+
+/// Float32 array read from a `.npy` file
+#[derive(Debug, PartialEq)]
+pub struct NpyArray {
+    pub shape: Vec<usize>,
+    pub data: Vec<f32>,
+}
+
+/// Parse a version 1–3 `.npy` file holding a little-endian float32 array in C order
+pub fn parse_npy(bytes: &[u8]) -> Result<NpyArray> {
+    if bytes.len() < 10 || &bytes[..6] != b"\x93NUMPY" {
+        bail!("Not a .npy file");
+    }
+    let (header_len, header_start) = match bytes[6] {
+        1 => (u16::from_le_bytes([bytes[8], bytes[9]]) as usize, 10),
+        2 | 3 if bytes.len() >= 12 => (u32::from_le_bytes([bytes[8], bytes[9], bytes[10], bytes[11]]) as usize, 12),
+        version => bail!("Unsupported .npy version {}", version),
+    };
+    let Some(header) = bytes.get(header_start..header_start + header_len) else {
+        bail!("Truncated .npy header");
+    };
+    let header = std::str::from_utf8(header)?;
+
+    let descr = header_value(header, "descr").unwrap_or_default();
+    if !descr.contains("<f4") {
+        bail!("Unsupported .npy dtype {}", descr);
+    }
+    if header_value(header, "fortran_order").is_some_and(|v| v.starts_with("True")) {
+        bail!("Fortran-ordered .npy arrays are not supported");
+    }
+
+    let shape_text = header_value(header, "shape").unwrap_or_default();
+    let shape = shape_text
+        .trim_start_matches('(')
+        .split(')')
+        .next()
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(str::parse)
+        .collect::<Result<Vec<usize>, _>>()?;
+
+    if shape.contains(&0) {
+        bail!("Empty .npy array with shape {:?}", shape);
+    }
+    let Some((count, data_len)) = shape
+        .iter()
+        .try_fold(1usize, |n, &d| n.checked_mul(d))
+        .and_then(|count| Some((count, count.checked_mul(4)?)))
+    else {
+        bail!(".npy shape {:?} is too large", shape);
+    };
+    let body = &bytes[header_start + header_len..];
+    if body.len() < data_len {
+        bail!("Truncated .npy data: expected {} values", count);
+    }
+    let data = body
+        .chunks_exact(4)
+        .take(count)
+        .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect();
+
+    Ok(NpyArray { shape, data })
+}
+
+/// Text following `'key':` in a `.npy` header dict
+fn header_value<'a>(header: &'a str, key: &str) -> Option<&'a str> {
+    let start = header.find(&format!("'{}'", key))? + key.len() + 2;
+    Some(header[start..].trim_start().strip_prefix(':')?.trim_start())
+}
+
+/// Difference between a decoded image and its reference
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ErrorStats {
+    pub rms: f64,
+    pub peak: f64,
+}
+
+/// Compare decoded frames against a frames × height × width × channels reference
+///
+/// Only the channels present in both are compared, so an opaque reference can
+/// be checked against a decode that carries an alpha channel, and vice versa.
+pub fn compare(frames: &[DecodedFloatFrame], reference: &NpyArray) -> Result<ErrorStats> {
+    let &[count, height, width, ref_channels] = reference.shape.as_slice() else {
+        bail!("Reference has shape {:?}, expected 4 dimensions", reference.shape);
+    };
+    if frames.len() != count {
+        bail!("Decoded {} frames, reference has {}", frames.len(), count);
+    }
+
+    let mut sum_sq = 0.0;
+    let mut peak = 0.0f64;
+    let mut samples = 0usize;
+    let Some(frame_len) = height.checked_mul(width).and_then(|n| n.checked_mul(ref_channels)) else {
+        bail!("Reference shape {:?} is too large", reference.shape);
+    };
+    if frame_len == 0 {
+        bail!("Reference has shape {:?}, which holds no samples", reference.shape);
+    }
+
+    for (frame, expected) in frames.iter().zip(reference.data.chunks_exact(frame_len)) {
+        if (frame.width, frame.height) != (width, height) {
+            bail!("Decoded {}×{}, reference is {}×{}", frame.width, frame.height, width, height);
+        }
+        let channels = frame.channels.min(ref_channels);
+        for (pixel, expected) in frame.samples.chunks_exact(frame.channels).zip(expected.chunks_exact(ref_channels)) {
+            for c in 0..channels {
+                let diff = (pixel[c] as f64 - expected[c] as f64).abs();
+                sum_sq += diff * diff;
+                peak = peak.max(diff);
+            }
+            samples += channels;
+        }
+    }
+
+    Ok(ErrorStats {
+        rms: if samples > 0 { (sum_sq / samples as f64).sqrt() } else { 0.0 },
+        peak,
+    })
+}
+
+/// Result of one conformance case
+#[derive(Debug, PartialEq)]
+pub enum Outcome {
+    Pass(ErrorStats),
+    /// Decoded, but outside the case's tolerances
+    Fail(ErrorStats),
+    /// The decode or the comparison failed outright
+    Error(String),
+}
+
+impl Outcome {
+    pub fn is_pass(&self) -> bool {
+        matches!(self, Outcome::Pass(_))
+    }
+}
+
+#[derive(Debug)]
+pub struct CaseResult {
+    pub name: String,
+    pub rms_limit: f64,
+    pub peak_limit: f64,
+    pub outcome: Outcome,
+}
+
+/// Test case directories under `root`, or under `root/testcases` if present
+pub fn find_cases(root: &Path) -> Result<Vec<PathBuf>> {
+    let testcases = root.join("testcases");
+    let dir = if testcases.is_dir() { testcases } else { root.to_path_buf() };
+
+    let mut cases: Vec<PathBuf> = std::fs::read_dir(&dir)?
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.join("input.jxl").is_file())
+        .collect();
+    cases.sort();
+    Ok(cases)
+}
+
+/// Tolerances from a case's `test.json`, falling back to the defaults
+fn tolerances(case: &Path) -> (f64, f64) {
+    let json: Option<serde_json::Value> = std::fs::read_to_string(case.join("test.json"))
+        .ok()
+        .and_then(|text| serde_json::from_str(&text).ok());
+    let get = |key: &str, default: f64| {
+        json.as_ref()
+            .and_then(|j| j.get(key))
+            .and_then(|v| v.as_f64())
+            .unwrap_or(default)
+    };
+    (get("rms_error", DEFAULT_RMS_ERROR), get("peak_error", DEFAULT_PEAK_ERROR))
+}
+
+/// Decode one case and compare it with its reference image
+pub fn run_case(case: &Path) -> CaseResult {
+    let name = case.file_name().unwrap_or_default().to_string_lossy().into_owned();
+    let (rms_limit, peak_limit) = tolerances(case);
+
+    let stats = std::fs::read(case.join("reference_image.npy"))
+        .map_err(anyhow::Error::from)
+        .and_then(|bytes| parse_npy(&bytes))
+        .and_then(|reference| {
+            let frames = catch_panic(|| decode_float_frames(case.join("input.jxl"), &DecodeOptions::default()))?;
+            compare(&frames, &reference)
+        });
+
+    let outcome = match stats {
+        Ok(stats) if stats.rms <= rms_limit && stats.peak <= peak_limit => Outcome::Pass(stats),
+        Ok(stats) => Outcome::Fail(stats),
+        Err(e) => Outcome::Error(e.to_string()),
+    };
+
+    CaseResult { name, rms_limit, peak_limit, outcome }
+}
+
+/// Run every case found under `root`
+pub fn run_suite(root: &Path) -> Result<Vec<CaseResult>> {
+    let cases = find_cases(root)?;
+    if cases.is_empty() {
+        bail!("No test cases found in {:?}", root);
+    }
+    Ok(cases.iter().map(|case| run_case(case)).collect())
+}
+
+/// Pass/fail table with measured errors against each case's limits
+pub fn format_matrix(results: &[CaseResult]) -> String {
+    let name_width = results.iter().map(|r| r.name.len()).max().unwrap_or(0).max(4);
+    let mut out = String::new();
+
+    let _ = writeln!(out, "{:<name_width$}  {:<6}  {:>21}  {:>21}", "case", "result", "rms (limit)", "peak (limit)");
+    for result in results {
+        let _ = match &result.outcome {
+            Outcome::Pass(stats) | Outcome::Fail(stats) => writeln!(
+                out,
+                "{:<name_width$}  {:<6}  {:>9.6} ({:>9.6})  {:>9.6} ({:>9.6})",
+                result.name,
+                if result.outcome.is_pass() { "PASS" } else { "FAIL" },
+                stats.rms,
+                result.rms_limit,
+                stats.peak,
+                result.peak_limit
+            ),
+            Outcome::Error(e) => writeln!(out, "{:<name_width$}  {:<6}  {}", result.name, "ERROR", e),
+        };
+    }
+
+    let passed = results.iter().filter(|r| r.outcome.is_pass()).count();
+    let _ = writeln!(out, "\n{} of {} cases passed", passed, results.len());
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Encode a float32 array the way `numpy.save` does
+    fn npy(shape: &[usize], data: &[f32]) -> Vec<u8> {
+        let shape_text: Vec<String> = shape.iter().map(|s| s.to_string()).collect();
+        let mut header = format!(
+            "{{'descr': '<f4', 'fortran_order': False, 'shape': ({},), }}",
+            shape_text.join(", ")
+        );
+        while (header.len() + 11) % 64 != 0 {
+            header.push(' ');
+        }
+        header.push('\n');
+
+        let mut bytes = b"\x93NUMPY\x01\x00".to_vec();
+        bytes.extend((header.len() as u16).to_le_bytes());
+        bytes.extend(header.as_bytes());
+        bytes.extend(data.iter().flat_map(|v| v.to_le_bytes()));
+        bytes
+    }
+
+    fn frame(width: usize, height: usize, channels: usize, samples: Vec<f32>) -> DecodedFloatFrame {
+        DecodedFloatFrame { width, height, channels, samples }
+    }
+
+    #[test]
+    fn test_parse_npy() {
+        let array = parse_npy(&npy(&[1, 2, 1, 3], &[0.0, 0.25, 0.5, 0.75, 1.0, 0.125])).unwrap();
+        assert_eq!(array.shape, vec![1, 2, 1, 3]);
+        assert_eq!(array.data, vec![0.0, 0.25, 0.5, 0.75, 1.0, 0.125]);
+
+        assert!(parse_npy(b"\x89PNG\r\n\x1a\n\0\0").is_err());
+        let mut truncated = npy(&[1, 2, 1, 3], &[0.0; 6]);
+        truncated.truncate(truncated.len() - 4);
+        assert!(parse_npy(&truncated).is_err());
+        let float64 = String::from_utf8_lossy(&npy(&[1], &[0.0])).replace("<f4", "<f8");
+        assert!(parse_npy(float64.as_bytes()).is_err());
+
+        // Empty and overflowing shapes are rejected rather than panicking
+        assert!(parse_npy(&npy(&[1, 0, 2, 3], &[])).is_err());
+        assert!(parse_npy(&npy(&[usize::MAX, 2], &[])).is_err());
+        assert!(parse_npy(&npy(&[usize::MAX / 2], &[])).is_err());
+    }
+
+    #[test]
+    fn test_compare() {
+        let reference = parse_npy(&npy(&[1, 1, 2, 3], &[0.0, 0.5, 1.0, 0.2, 0.2, 0.2])).unwrap();
+
+        let exact = frame(2, 1, 3, vec![0.0, 0.5, 1.0, 0.2, 0.2, 0.2]);
+        assert_eq!(compare(&[exact], &reference).unwrap(), ErrorStats::default());
+
+        // Alpha in the decode is ignored against an RGB reference
+        let off = frame(2, 1, 4, vec![0.0, 0.5, 1.0, 1.0, 0.2, 0.2, 0.5, 1.0]);
+        let stats = compare(&[off], &reference).unwrap();
+        assert!((stats.peak - 0.3).abs() < 1e-6);
+        assert!((stats.rms - (0.09f64 / 6.0).sqrt()).abs() < 1e-6);
+
+        assert!(compare(&[], &reference).is_err());
+        assert!(compare(&[frame(1, 2, 3, vec![0.0; 6])], &reference).is_err());
+
+        let empty = NpyArray { shape: vec![1, 0, 2, 3], data: Vec::new() };
+        assert!(compare(&[frame(2, 0, 3, Vec::new())], &empty).is_err());
+        let huge = NpyArray { shape: vec![1, usize::MAX, 2, 3], data: Vec::new() };
+        assert!(compare(&[frame(2, 1, 3, vec![0.0; 6])], &huge).is_err());
+    }
+
+    #[test]
+    fn test_format_matrix() {
+        let results = vec![
+            CaseResult {
+                name: "bicycles".into(),
+                rms_limit: 0.001,
+                peak_limit: 0.004,
+                outcome: Outcome::Pass(ErrorStats { rms: 0.0002, peak: 0.001 }),
+            },
+            CaseResult {
+                name: "cafe".into(),
+                rms_limit: 0.001,
+                peak_limit: 0.004,
+                outcome: Outcome::Error("Decoded 1 frames, reference has 2".into()),
+            },
+        ];
+
+        let matrix = format_matrix(&results);
+        assert!(matrix.lines().nth(1).unwrap().starts_with("bicycles  PASS"));
+        assert!(matrix.contains("cafe      ERROR   Decoded 1 frames"));
+        assert!(matrix.ends_with("1 of 2 cases passed\n"));
+    }
+}
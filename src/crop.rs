@@ -0,0 +1,126 @@
+//! Selecting a region of the image and exporting it
+//!
+//! Selections are kept in image pixels, so they do not depend on how large
+//! the image is drawn and can be quoted exactly in bug reports.
+
+use anyhow::Result;
+use eframe::egui::Pos2;
+use std::fmt;
+use std::path::Path;
+
+/// A rectangle of image pixels
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Selection {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Selection {
+    /// Pixels covered by the rectangle between two corners in 0..1 image
+    /// coordinates, clamped to the image; `None` if nothing is covered
+    pub fn from_points(a: Pos2, b: Pos2, image_size: (u32, u32)) -> Option<Self> {
+        let (width, height) = (image_size.0 as f32, image_size.1 as f32);
+        let x0 = (a.x.min(b.x) * width).floor().clamp(0.0, width) as u32;
+        let y0 = (a.y.min(b.y) * height).floor().clamp(0.0, height) as u32;
+        let x1 = (a.x.max(b.x) * width).ceil().clamp(0.0, width) as u32;
+        let y1 = (a.y.max(b.y) * height).ceil().clamp(0.0, height) as u32;
+
+        (x1 > x0 && y1 > y0).then_some(Self {
+            x: x0,
+            y: y0,
+            width: x1 - x0,
+            height: y1 - y0,
+        })
+    }
+
+    /// Lies within an image of `image_size`, e.g. still after a reload
+    pub fn fits(&self, image_size: (u32, u32)) -> bool {
+        self.x + self.width <= image_size.0 && self.y + self.height <= image_size.1
+    }
+
+    /// Corners in 0..1 image coordinates, for drawing
+    pub fn normalized(&self, image_size: (u32, u32)) -> (Pos2, Pos2) {
+        let (width, height) = (image_size.0 as f32, image_size.1 as f32);
+        (
+            Pos2::new(self.x as f32 / width, self.y as f32 / height),
+            Pos2::new((self.x + self.width) as f32 / width, (self.y + self.height) as f32 / height),
+        )
+    }
+
+    /// Copy the selected pixels out of an RGBA8 image `image_width` pixels wide
+    pub fn crop(&self, rgba: &[u8], image_width: u32) -> Vec<u8> {
+        let row_bytes = self.width as usize * 4;
+        let mut out = Vec::with_capacity(row_bytes * self.height as usize);
+        for y in self.y..self.y + self.height {
+            let start = (y as usize * image_width as usize + self.x as usize) * 4;
+            out.extend_from_slice(&rgba[start..start + row_bytes]);
+        }
+        out
+    }
+}
+
+impl fmt::Display for Selection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}×{} at ({}, {})", self.width, self.height, self.x, self.y)
+    }
+}
+
+/// Undo alpha premultiplication in place, for consumers expecting straight alpha
+pub fn unpremultiply(rgba: &mut [u8]) {
+    for px in rgba.chunks_exact_mut(4) {
+        let a = px[3] as u32;
+        if a == 0 || a == 255 {
+            continue;
+        }
+        for c in &mut px[..3] {
+            *c = ((*c as u32 * 255 + a / 2) / a).min(255) as u8;
+        }
+    }
+}
+
+/// Write straight-alpha RGBA8 pixels as a PNG file
+pub fn save_png(path: &Path, rgba: &[u8], width: u32, height: u32) -> Result<()> {
+    image::save_buffer_with_format(path, rgba, width, height, image::ExtendedColorType::Rgba8, image::ImageFormat::Png)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_points() {
+        // Dragging up and to the left selects the same pixels
+        let a = Pos2::new(0.75, 0.5);
+        let b = Pos2::new(0.125, 0.25);
+        let selection = Selection::from_points(a, b, (200, 100)).unwrap();
+        assert_eq!(selection, Selection { x: 25, y: 25, width: 125, height: 25 });
+        assert_eq!(selection.to_string(), "125×25 at (25, 25)");
+        assert_eq!(Selection::from_points(b, a, (200, 100)), Some(selection));
+        assert!(selection.fits((200, 100)));
+        assert!(!selection.fits((149, 100)));
+
+        // Dragging past the edge stops at the image border
+        let clamped = Selection::from_points(Pos2::new(0.875, -0.2), Pos2::new(1.5, 1.5), (200, 100)).unwrap();
+        assert_eq!(clamped, Selection { x: 175, y: 0, width: 25, height: 100 });
+
+        assert_eq!(Selection::from_points(Pos2::new(1.2, 0.0), Pos2::new(1.5, 1.0), (200, 100)), None);
+    }
+
+    #[test]
+    fn test_crop() {
+        // 3×2 image where every channel holds its pixel index
+        let rgba: Vec<u8> = (0..6).flat_map(|i| [i; 4]).collect();
+        let selection = Selection { x: 1, y: 0, width: 2, height: 2 };
+        assert_eq!(selection.crop(&rgba, 3), [[1; 4], [2; 4], [4; 4], [5; 4]].concat());
+    }
+
+    #[test]
+    fn test_unpremultiply() {
+        let mut rgba = vec![64, 32, 0, 128, 10, 20, 30, 255, 5, 5, 5, 0];
+        unpremultiply(&mut rgba);
+        assert_eq!(rgba, vec![128, 64, 0, 128, 10, 20, 30, 255, 5, 5, 5, 0]);
+    }
+}
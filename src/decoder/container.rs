@@ -0,0 +1,218 @@
+//! ISOBMFF container parsing for JPEG XL files
+//!
+//! A JXL file is either a bare codestream (starting with `FF 0A`) or an ISOBMFF
+//! container of boxes (`JXL `, `ftyp`, `jxlc`/`jxlp`, `Exif`, `xml `, `jumb`, ...).
+
+use anyhow::Result;
+use std::ops::Range;
+
+/// Signature of a bare JPEG XL codestream
+pub const CODESTREAM_SIGNATURE: [u8; 2] = [0xFF, 0x0A];
+
+/// Signature box that starts every JPEG XL container
+pub const CONTAINER_SIGNATURE: [u8; 12] = [
+    0x00, 0x00, 0x00, 0x0C, b'J', b'X', b'L', b' ', 0x0D, 0x0A, 0x87, 0x0A,
+];
+
+/// Deepest `jumb` superbox nesting that is parsed; deeper boxes are listed
+/// without children
+pub const MAX_BOX_DEPTH: usize = 16;
+
+/// Top-level layout of a JXL file
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FileKind {
+    Codestream,
+    Container,
+    Unknown,
+}
+
+/// A single box in the container
+#[derive(Clone, Debug)]
+pub struct JxlBox {
+    pub box_type: [u8; 4],
+    /// Offset of the box header from the start of the file
+    pub offset: u64,
+    /// Total box size including the header
+    pub size: u64,
+    /// Byte range of the payload within the file
+    pub payload: Range<usize>,
+    /// The declared size runs past the end of the file
+    pub truncated: bool,
+    /// Nested boxes (only parsed for `jumb` superboxes)
+    pub children: Vec<JxlBox>,
+}
+
+impl JxlBox {
+    /// Box type as a printable string, e.g. `"jxlc"` or `"xml "`
+    pub fn type_str(&self) -> String {
+        String::from_utf8_lossy(&self.box_type).to_string()
+    }
+
+    /// Payload bytes of this box
+    pub fn data<'a>(&self, bytes: &'a [u8]) -> &'a [u8] {
+        &bytes[self.payload.clone()]
+    }
+
+    /// For `brob` boxes, the type of the Brotli-compressed inner box
+    pub fn brob_inner_type(&self, bytes: &[u8]) -> Option<[u8; 4]> {
+        if &self.box_type != b"brob" {
+            return None;
+        }
+        self.data(bytes).get(..4).map(|t| [t[0], t[1], t[2], t[3]])
+    }
+}
+
+/// Determine whether the bytes are a bare codestream or a container
+pub fn detect_kind(bytes: &[u8]) -> FileKind {
+    if bytes.starts_with(&CODESTREAM_SIGNATURE) {
+        FileKind::Codestream
+    } else if bytes.starts_with(&CONTAINER_SIGNATURE) {
+        FileKind::Container
+    } else {
+        FileKind::Unknown
+    }
+}
+
+/// Parse all top-level boxes of a container file
+///
+/// Returns an empty list for bare codestreams.
+pub fn parse_boxes(bytes: &[u8]) -> Result<Vec<JxlBox>> {
+    match detect_kind(bytes) {
+        FileKind::Codestream => Ok(Vec::new()),
+        FileKind::Container => parse_box_range(bytes, 0..bytes.len(), 0),
+        FileKind::Unknown => anyhow::bail!("Not a JPEG XL file"),
+    }
+}
+
+/// Parse consecutive boxes within `range`, `depth` superboxes deep
+fn parse_box_range(bytes: &[u8], range: Range<usize>, depth: usize) -> Result<Vec<JxlBox>> {
+    let mut boxes = Vec::new();
+    let mut pos = range.start;
+
+    while pos < range.end {
+        let remaining = range.end - pos;
+        if remaining < 8 {
+            anyhow::bail!("Truncated box header at offset {}", pos);
+        }
+
+        let mut size = u32::from_be_bytes(bytes[pos..pos + 4].try_into()?) as u64;
+        let box_type: [u8; 4] = bytes[pos + 4..pos + 8].try_into()?;
+        let mut header_size = 8u64;
+
+        if size == 1 {
+            // 64-bit extended size follows the type
+            if remaining < 16 {
+                anyhow::bail!("Truncated extended box header at offset {}", pos);
+            }
+            size = u64::from_be_bytes(bytes[pos + 8..pos + 16].try_into()?);
+            header_size = 16;
+        } else if size == 0 {
+            // Box extends to the end of the file (only allowed for the last box)
+            if depth > 0 {
+                anyhow::bail!("Open-ended box inside superbox at offset {}", pos);
+            }
+            size = remaining as u64;
+        }
+
+        if size < header_size {
+            anyhow::bail!("Invalid box size {} at offset {}", size, pos);
+        }
+
+        let truncated = size > remaining as u64;
+        let end = if truncated {
+            range.end
+        } else {
+            pos + size as usize
+        };
+        let payload = pos + header_size as usize..end;
+
+        let children = if &box_type == b"jumb" && !truncated && depth < MAX_BOX_DEPTH {
+            parse_box_range(bytes, payload.clone(), depth + 1).unwrap_or_default()
+        } else {
+            Vec::new()
+        };
+
+        boxes.push(JxlBox {
+            box_type,
+            offset: pos as u64,
+            size,
+            payload,
+            truncated,
+            children,
+        });
+
+        pos = end;
+    }
+
+    Ok(boxes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_box(box_type: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+        let mut out = ((payload.len() + 8) as u32).to_be_bytes().to_vec();
+        out.extend_from_slice(box_type);
+        out.extend_from_slice(payload);
+        out
+    }
+
+    #[test]
+    fn test_detect_kind() {
+        assert_eq!(detect_kind(&[0xFF, 0x0A, 0x00]), FileKind::Codestream);
+        assert_eq!(detect_kind(&CONTAINER_SIGNATURE), FileKind::Container);
+        assert_eq!(detect_kind(b"\x89PNG"), FileKind::Unknown);
+    }
+
+    #[test]
+    fn test_parse_container() {
+        let mut file = CONTAINER_SIGNATURE.to_vec();
+        file.extend(make_box(b"ftyp", b"jxl \0\0\0\0jxl "));
+        file.extend(make_box(b"xml ", b"<x/>"));
+        // Last box with size 0 runs to the end of the file
+        file.extend_from_slice(&[0, 0, 0, 0]);
+        file.extend_from_slice(b"jxlc");
+        file.extend_from_slice(&[0xFF, 0x0A, 1, 2, 3]);
+
+        let boxes = parse_boxes(&file).unwrap();
+        let types: Vec<String> = boxes.iter().map(|b| b.type_str()).collect();
+        assert_eq!(types, ["JXL ", "ftyp", "xml ", "jxlc"]);
+        assert_eq!(boxes[2].data(&file), b"<x/>");
+        assert_eq!(boxes[3].data(&file), &[0xFF, 0x0A, 1, 2, 3]);
+        assert!(!boxes[3].truncated);
+    }
+
+    #[test]
+    fn test_truncated_box() {
+        let mut file = CONTAINER_SIGNATURE.to_vec();
+        let mut jxlc = make_box(b"jxlc", &[0u8; 32]);
+        jxlc.truncate(20);
+        file.extend(jxlc);
+
+        let boxes = parse_boxes(&file).unwrap();
+        assert!(boxes[1].truncated);
+        assert_eq!(boxes[1].size, 40);
+        assert_eq!(boxes[1].payload.len(), 12);
+    }
+
+    #[test]
+    fn test_nesting_depth() {
+        let mut jumb = make_box(b"xml ", b"<x/>");
+        for _ in 0..MAX_BOX_DEPTH + 4 {
+            jumb = make_box(b"jumb", &jumb);
+        }
+        let mut file = CONTAINER_SIGNATURE.to_vec();
+        file.extend(jumb);
+
+        let boxes = parse_boxes(&file).unwrap();
+        let mut depth = 0;
+        let mut level = &boxes[1];
+        while let Some(child) = level.children.first() {
+            depth += 1;
+            level = child;
+        }
+        assert_eq!(depth, MAX_BOX_DEPTH);
+        assert_eq!(level.type_str(), "jumb");
+    }
+}
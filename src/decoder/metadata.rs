@@ -0,0 +1,280 @@
+//! Exif, XMP and JUMBF metadata extraction from JXL container boxes
+
+use super::container::{self, JxlBox};
+use anyhow::Result;
+use std::io::Read;
+use std::path::Path;
+
+/// Largest decompressed `brob` box; Brotli can expand a few KB into gigabytes
+const MAX_BROB_SIZE: usize = 64 * 1024 * 1024;
+
+/// A decoded Exif field
+#[derive(Clone, Debug)]
+pub struct ExifTag {
+    pub ifd: String,
+    pub name: String,
+    pub value: String,
+}
+
+/// A JUMBF superbox, summarised for display
+#[derive(Clone, Debug)]
+pub struct JumbfEntry {
+    pub label: Option<String>,
+    pub size: u64,
+    pub content_types: Vec<String>,
+}
+
+/// Metadata carried in the container boxes of a JXL file
+#[derive(Clone, Debug, Default)]
+pub struct ContainerMetadata {
+    pub exif: Option<Result<Vec<ExifTag>, String>>,
+    /// Orientation tag of the Exif data; JXL viewers ignore it in favour of
+    /// the orientation in the image header
+    pub exif_orientation: Option<u8>,
+    /// Pretty-printed XMP packet
+    pub xmp: Option<String>,
+    pub jumbf: Vec<JumbfEntry>,
+    /// Size of the `jbrd` JPEG bitstream reconstruction data, if present
+    ///
+    /// jxl-rs cannot reconstruct the original JPEG yet, so only presence and
+    /// size are reported.
+    pub jpeg_reconstruction_size: Option<u64>,
+}
+
+impl ContainerMetadata {
+    /// No Exif, XMP or JUMBF metadata was found
+    pub fn is_empty(&self) -> bool {
+        self.exif.is_none() && self.xmp.is_none() && self.jumbf.is_empty()
+    }
+}
+
+/// Read metadata boxes from a JXL file on disk
+pub fn read_metadata_from_file<P: AsRef<Path>>(path: P) -> Result<ContainerMetadata> {
+    let bytes = std::fs::read(path)?;
+    read_metadata(&bytes)
+}
+
+/// Extract Exif, XMP and JUMBF metadata from the bytes of a JXL file
+///
+/// Brotli-compressed (`brob`) metadata boxes are decompressed transparently.
+pub fn read_metadata(bytes: &[u8]) -> Result<ContainerMetadata> {
+    let mut metadata = ContainerMetadata::default();
+
+    for jxl_box in container::parse_boxes(bytes)? {
+        let (box_type, payload) = match jxl_box.brob_inner_type(bytes) {
+            Some(inner_type) => match decompress_brob(jxl_box.data(bytes), MAX_BROB_SIZE) {
+                Ok(data) => (inner_type, data),
+                Err(e) => {
+                    log::warn!("Failed to decompress brob box: {}", e);
+                    continue;
+                }
+            },
+            None => (jxl_box.box_type, jxl_box.data(bytes).to_vec()),
+        };
+
+        match &box_type {
+            b"Exif" => match parse_exif(&payload) {
+                Ok((tags, orientation)) => {
+                    metadata.exif = Some(Ok(tags));
+                    metadata.exif_orientation = orientation;
+                }
+                Err(e) => metadata.exif = Some(Err(e.to_string())),
+            },
+            b"xml " => {
+                let xml = String::from_utf8_lossy(&payload);
+                metadata.xmp = Some(pretty_print_xml(&xml));
+            }
+            b"jumb" => {
+                metadata.jumbf.push(summarize_jumbf(&jxl_box, bytes));
+            }
+            b"jbrd" => {
+                metadata.jpeg_reconstruction_size = Some(payload.len() as u64);
+            }
+            _ => {}
+        }
+    }
+
+    Ok(metadata)
+}
+
+/// Decompress the payload of a `brob` box (4-byte inner type followed by Brotli
+/// data), failing if it holds more than `limit` bytes
+fn decompress_brob(data: &[u8], limit: usize) -> Result<Vec<u8>> {
+    if data.len() < 4 {
+        anyhow::bail!("brob box too small");
+    }
+    let mut out = Vec::new();
+    brotli_decompressor::Decompressor::new(&data[4..], 4096)
+        .take(limit as u64 + 1)
+        .read_to_end(&mut out)?;
+    if out.len() > limit {
+        anyhow::bail!("brob box decompresses to more than {} bytes", limit);
+    }
+    Ok(out)
+}
+
+/// Parse the payload of an `Exif` box
+///
+/// The payload starts with a 4-byte big-endian offset to the TIFF header.
+/// Returns the tags along with the value of the Orientation tag, if any.
+fn parse_exif(payload: &[u8]) -> Result<(Vec<ExifTag>, Option<u8>)> {
+    if payload.len() < 4 {
+        anyhow::bail!("Exif box too small");
+    }
+    let offset = u32::from_be_bytes(payload[..4].try_into()?) as usize;
+    let tiff = payload
+        .get(4 + offset..)
+        .ok_or_else(|| anyhow::anyhow!("Invalid Exif TIFF header offset {}", offset))?;
+
+    let exif = exif::Reader::new().read_raw(tiff.to_vec())?;
+    let tags = exif
+        .fields()
+        .map(|field| ExifTag {
+            ifd: field.ifd_num.to_string(),
+            name: field.tag.to_string(),
+            value: field.display_value().with_unit(&exif).to_string(),
+        })
+        .collect();
+
+    let orientation = exif
+        .get_field(exif::Tag::Orientation, exif::In::PRIMARY)
+        .and_then(|field| field.value.get_uint(0))
+        .and_then(|value| u8::try_from(value).ok());
+
+    Ok((tags, orientation))
+}
+
+/// Describe a `jumb` superbox from its description (`jumd`) box
+fn summarize_jumbf(jxl_box: &JxlBox, bytes: &[u8]) -> JumbfEntry {
+    let label = jxl_box
+        .children
+        .iter()
+        .find(|b| &b.box_type == b"jumd")
+        .and_then(|jumd| {
+            // 16-byte content type UUID, 1 toggle byte, then an optional null-terminated label
+            let data = jumd.data(bytes);
+            let toggles = *data.get(16)?;
+            if toggles & 0x02 == 0 {
+                return None;
+            }
+            let label = data.get(17..)?;
+            let end = label.iter().position(|&c| c == 0).unwrap_or(label.len());
+            Some(String::from_utf8_lossy(&label[..end]).to_string())
+        });
+
+    JumbfEntry {
+        label,
+        size: jxl_box.size,
+        content_types: jxl_box
+            .children
+            .iter()
+            .filter(|b| &b.box_type != b"jumd")
+            .map(|b| b.type_str())
+            .collect(),
+    }
+}
+
+/// Re-indent an XML document with one element per line
+pub fn pretty_print_xml(xml: &str) -> String {
+    let mut out = String::new();
+    let mut depth = 0usize;
+    let mut rest = xml.trim();
+
+    while !rest.is_empty() {
+        let (token, remainder) = if rest.starts_with('<') {
+            match rest.find('>') {
+                Some(end) => rest.split_at(end + 1),
+                None => (rest, ""),
+            }
+        } else {
+            match rest.find('<') {
+                Some(start) => rest.split_at(start),
+                None => (rest, ""),
+            }
+        };
+        rest = remainder;
+
+        let token = token.trim();
+        if token.is_empty() {
+            continue;
+        }
+
+        let is_closing = token.starts_with("</");
+        let is_opening = token.starts_with('<')
+            && !is_closing
+            && !token.ends_with("/>")
+            && !token.starts_with("<?")
+            && !token.starts_with("<!");
+
+        if is_closing {
+            depth = depth.saturating_sub(1);
+        }
+
+        out.push_str(&"  ".repeat(depth));
+        out.push_str(token);
+        out.push('\n');
+
+        if is_opening {
+            depth += 1;
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pretty_print_xml() {
+        let xml = "<?xpacket begin=''?><x:xmpmeta><rdf:RDF><rdf:Description a='1'/>\
+                   <dc:title>Hello</dc:title></rdf:RDF></x:xmpmeta>";
+        let expected = "<?xpacket begin=''?>\n\
+                        <x:xmpmeta>\n  \
+                          <rdf:RDF>\n    \
+                            <rdf:Description a='1'/>\n    \
+                            <dc:title>\n      \
+                              Hello\n    \
+                            </dc:title>\n  \
+                          </rdf:RDF>\n\
+                        </x:xmpmeta>\n";
+        assert_eq!(pretty_print_xml(xml), expected);
+    }
+
+    #[test]
+    fn test_brob_limit() {
+        // Inner type, then a Brotli stream of one uncompressed meta-block and an empty last one
+        let mut data = b"Exif".to_vec();
+        data.extend_from_slice(&[0x40, 0x00, 0x10]);
+        data.extend_from_slice(b"hello");
+        data.push(0x03);
+
+        assert_eq!(decompress_brob(&data, 5).unwrap(), b"hello");
+        assert!(decompress_brob(&data, 4).is_err());
+        assert!(decompress_brob(b"Exi", 5).is_err());
+    }
+
+    #[test]
+    fn test_exif_offset() {
+        // Minimal big-endian TIFF with an empty IFD0, preceded by a zero offset
+        let mut payload = vec![0, 0, 0, 0];
+        payload.extend_from_slice(b"MM\0\x2a\0\0\0\x08\0\0\0\0\0\0");
+        let (tags, orientation) = parse_exif(&payload).unwrap();
+        assert!(tags.is_empty());
+        assert_eq!(orientation, None);
+
+        assert!(parse_exif(&[0, 0, 1, 0]).is_err());
+    }
+
+    #[test]
+    fn test_exif_orientation() {
+        // IFD0 with a single Orientation (0x0112) SHORT entry set to 6
+        let mut payload = vec![0, 0, 0, 0];
+        payload.extend_from_slice(b"MM\0\x2a\0\0\0\x08");
+        payload.extend_from_slice(&[0, 1, 0x01, 0x12, 0, 3, 0, 0, 0, 1, 0, 6, 0, 0, 0, 0, 0, 0]);
+        let (tags, orientation) = parse_exif(&payload).unwrap();
+        assert_eq!(tags.len(), 1);
+        assert_eq!(orientation, Some(6));
+    }
+}
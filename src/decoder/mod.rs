@@ -1,5 +1,9 @@
 pub mod worker;
 pub mod rgb_conversion;
+pub mod container;
+pub mod metadata;
+pub mod structure;
+pub mod timing;
 
 use std::time::Duration;
 
@@ -10,7 +14,27 @@ pub struct DecodedFrame {
     pub width: u32,
     pub height: u32,
     pub decode_time: Duration,
-    pub duration_ms: u32, // Frame duration for animations
+    /// Conversion to RGBA8, not included in `decode_time`
+    pub conversion_time: Duration,
+    pub duration_ms: f64, // Frame duration for animations, derived from whole ticks
+}
+
+/// A frame as interleaved f32 samples in [0, 1], without alpha premultiplication
+#[derive(Clone)]
+pub struct DecodedFloatFrame {
+    pub width: usize,
+    pub height: usize,
+    /// 1 (gray), 2 (gray + alpha), 3 (RGB) or 4 (RGBA)
+    pub channels: usize,
+    pub samples: Vec<f32>,
+}
+
+/// A single frame decoded without coalescing
+#[derive(Clone)]
+pub struct DecodedLayer {
+    pub name: String,
+    pub frame: DecodedFrame,
+    pub is_cropped: bool, // Layer is smaller than the image canvas
 }
 
 /// Metadata about the decoded image
@@ -22,6 +46,10 @@ pub struct ImageMetadata {
     pub has_animation: bool,
     pub frame_count: usize,
     pub loop_count: u32,
+    pub tick_rate: Option<timing::TickRate>,
+    pub have_timecodes: bool,
+    /// EXIF orientation value (1-8) from the image header
+    pub orientation: u8,
 }
 
 /// Result of decoding an image (single or animated)
@@ -1,4 +1,5 @@
 use jxl::{api::JxlColorType, image::Image};
+use rayon::prelude::*;
 
 /// Convert f32 [0,1] to u8 [0,255]
 #[inline]
@@ -26,125 +27,63 @@ pub fn jxl_to_rgba8(
     height: usize,
 ) -> Vec<u8> {
     let mut rgba = vec![0u8; width * height * 4];
+    if width == 0 {
+        return rgba;
+    }
 
-    match color_type {
-        JxlColorType::Grayscale => {
-            // Single channel -> RGB (same value for all) + opaque alpha
-            if channels.is_empty() {
-                log::warn!("No channels provided for Grayscale image");
-                return rgba;
-            }
-
-            for y in 0..height {
-                let gray_row = channels[0].row(y);
-                for x in 0..width {
-                    let idx = (y * width + x) * 4;
-                    let gray = f32_to_u8(gray_row[x]);
+    // A channel smaller than the image would be indexed out of bounds below
+    if channels.iter().take(4).any(|c| c.size().0 < width || c.size().1 < height) {
+        log::warn!("Channels are smaller than the {}x{} image", width, height);
+        return rgba;
+    }
 
-                    rgba[idx] = gray;     // R
-                    rgba[idx + 1] = gray; // G
-                    rgba[idx + 2] = gray; // B
-                    rgba[idx + 3] = 255;  // A (opaque)
-                }
-            }
-        }
-        JxlColorType::GrayscaleAlpha => {
-            // Two channels -> RGB (same value) + alpha
-            if channels.len() < 2 {
-                log::warn!("Insufficient channels for GrayscaleAlpha image");
-                return rgba;
-            }
+    let required = match color_type {
+        JxlColorType::Grayscale => 1,
+        JxlColorType::GrayscaleAlpha => 2,
+        JxlColorType::Rgb | JxlColorType::Bgr => 3, // Alpha comes from a fourth channel, if any
+        JxlColorType::Rgba | JxlColorType::Bgra => 4,
+    };
+    if channels.len() < required {
+        log::warn!("Insufficient channels for {:?} image", color_type);
+        return rgba;
+    }
 
-            for y in 0..height {
-                let gray_row = channels[0].row(y);
-                let alpha_row = channels[1].row(y);
-                for x in 0..width {
-                    let idx = (y * width + x) * 4;
+    // Rows are collected up front so threads share plain slices, not the images
+    let rows: Vec<Vec<&[f32]>> = channels.iter()
+        .take(4)
+        .map(|c| (0..height).map(|y| &c.row(y)[..width]).collect())
+        .collect();
+    let is_bgr = matches!(color_type, JxlColorType::Bgr | JxlColorType::Bgra);
+
+    // Rows are independent, so they are converted in parallel
+    rgba.par_chunks_mut(width * 4).enumerate().for_each(|(y, out)| {
+        match color_type {
+            JxlColorType::Grayscale | JxlColorType::GrayscaleAlpha => {
+                // Gray -> RGB (same value for all), with alpha if present
+                let gray_row = rows[0][y];
+                let alpha_row = matches!(color_type, JxlColorType::GrayscaleAlpha).then(|| rows[1][y]);
+                for (x, px) in out.chunks_exact_mut(4).enumerate() {
                     let gray = f32_to_u8(gray_row[x]);
-                    let alpha = f32_to_u8(alpha_row[x]);
-
-                    rgba[idx] = gray;     // R
-                    rgba[idx + 1] = gray; // G
-                    rgba[idx + 2] = gray; // B
-                    rgba[idx + 3] = alpha; // A
-                }
-            }
-        }
-        JxlColorType::Rgb | JxlColorType::Bgr => {
-            // Three or four channels (RGB/BGR + optional alpha from extra channel)
-            if channels.len() < 3 {
-                log::warn!("Insufficient channels for RGB/BGR image");
-                return rgba;
-            }
-
-            let is_bgr = matches!(color_type, JxlColorType::Bgr);
-            let has_alpha = channels.len() >= 4;
-
-            for y in 0..height {
-                let row0 = channels[0].row(y);
-                let row1 = channels[1].row(y);
-                let row2 = channels[2].row(y);
-                let row3 = if has_alpha { Some(channels[3].row(y)) } else { None };
-
-                for x in 0..width {
-                    let idx = (y * width + x) * 4;
-
-                    if is_bgr {
-                        // BGR -> RGB
-                        rgba[idx] = f32_to_u8(row2[x]);     // R from B channel
-                        rgba[idx + 1] = f32_to_u8(row1[x]); // G
-                        rgba[idx + 2] = f32_to_u8(row0[x]); // B from R channel
-                    } else {
-                        // RGB
-                        rgba[idx] = f32_to_u8(row0[x]);     // R
-                        rgba[idx + 1] = f32_to_u8(row1[x]); // G
-                        rgba[idx + 2] = f32_to_u8(row2[x]); // B
-                    }
-
-                    // Use alpha channel if present, otherwise opaque
-                    rgba[idx + 3] = if let Some(alpha_row) = row3 {
-                        f32_to_u8(alpha_row[x])
-                    } else {
-                        255
-                    };
+                    px[0] = gray;
+                    px[1] = gray;
+                    px[2] = gray;
+                    px[3] = alpha_row.map_or(255, |a| f32_to_u8(a[x]));
                 }
             }
-        }
-        JxlColorType::Rgba | JxlColorType::Bgra => {
-            // Four channels (RGBA or BGRA)
-            if channels.len() < 4 {
-                log::warn!("Insufficient channels for RGBA/BGRA image");
-                return rgba;
-            }
-
-            let is_bgra = matches!(color_type, JxlColorType::Bgra);
-
-            for y in 0..height {
-                let row0 = channels[0].row(y);
-                let row1 = channels[1].row(y);
-                let row2 = channels[2].row(y);
-                let row3 = channels[3].row(y);
-
-                for x in 0..width {
-                    let idx = (y * width + x) * 4;
-
-                    if is_bgra {
-                        // BGRA -> RGBA
-                        rgba[idx] = f32_to_u8(row2[x]);     // R from B channel
-                        rgba[idx + 1] = f32_to_u8(row1[x]); // G
-                        rgba[idx + 2] = f32_to_u8(row0[x]); // B from R channel
-                        rgba[idx + 3] = f32_to_u8(row3[x]); // A
-                    } else {
-                        // RGBA
-                        rgba[idx] = f32_to_u8(row0[x]);     // R
-                        rgba[idx + 1] = f32_to_u8(row1[x]); // G
-                        rgba[idx + 2] = f32_to_u8(row2[x]); // B
-                        rgba[idx + 3] = f32_to_u8(row3[x]); // A
-                    }
+            _ => {
+                // RGB/BGR with optional alpha from an extra channel, or RGBA/BGRA
+                let (r_row, b_row) = if is_bgr { (rows[2][y], rows[0][y]) } else { (rows[0][y], rows[2][y]) };
+                let g_row = rows[1][y];
+                let alpha_row = rows.get(3).map(|a| a[y]);
+                for (x, px) in out.chunks_exact_mut(4).enumerate() {
+                    px[0] = f32_to_u8(r_row[x]);
+                    px[1] = f32_to_u8(g_row[x]);
+                    px[2] = f32_to_u8(b_row[x]);
+                    px[3] = alpha_row.map_or(255, |a| f32_to_u8(a[x]));
                 }
             }
         }
-    }
+    });
 
     rgba
 }
@@ -197,4 +136,34 @@ mod tests {
         assert_eq!(rgba[2], 127); // B
         assert_eq!(rgba[3], 255); // A (opaque)
     }
+
+    #[test]
+    fn test_bgra_rows() {
+        // Every row gets its own values, so rows landing in the wrong place show up
+        let (width, height) = (3, 4);
+        let channels: Vec<Image<f32>> = (0..4)
+            .map(|c| {
+                let mut img = Image::<f32>::new((width, height)).unwrap();
+                for y in 0..height {
+                    img.row_mut(y).fill((c * 4 + y) as f32 / 16.0);
+                }
+                img
+            })
+            .collect();
+        let rgba = jxl_to_rgba8(&channels, JxlColorType::Bgra, width, height);
+
+        for y in 0..height {
+            let px = &rgba[y * width * 4..y * width * 4 + 4];
+            let expected = |c: usize| f32_to_u8((c * 4 + y) as f32 / 16.0);
+            assert_eq!(px, [expected(2), expected(1), expected(0), expected(3)], "row {}", y);
+        }
+    }
+
+    #[test]
+    fn test_short_channels() {
+        // Channels smaller than the image must not be indexed past their end
+        let channels: Vec<Image<f32>> = (0..4).map(|_| Image::<f32>::new((2, 1)).unwrap()).collect();
+        let rgba = jxl_to_rgba8(&channels, JxlColorType::Rgba, 2, 2);
+        assert_eq!(rgba, vec![0; 16]);
+    }
 }
@@ -0,0 +1,208 @@
+//! Container and codestream structure inspection
+//!
+//! jxl-rs only exposes the name, duration and size of each frame header through
+//! its public API. Frame type, blend mode and upsampling are not available, so
+//! the inspector reports what the decoder gives us plus the full box layout.
+
+use super::container::{self, FileKind, JxlBox};
+use super::timing::{self, FrameDuration, TickRate};
+use anyhow::Result;
+use jxl::api::{JxlDecoder, JxlDecoderOptions, ProcessingResult};
+use std::io::Cursor;
+use std::path::Path;
+
+/// A `jxlp` partial codestream box
+#[derive(Clone, Debug)]
+pub struct PartialCodestream {
+    pub index: u32,
+    pub is_last: bool,
+    pub offset: u64,
+    pub size: u64,
+}
+
+/// Header facts for a single (non-coalesced) frame
+#[derive(Clone, Debug)]
+pub struct FrameInfo {
+    pub name: String,
+    /// Only set for animations
+    pub duration: Option<FrameDuration>,
+    pub size: (usize, usize),
+    /// The frame covers only part of the image canvas
+    pub is_cropped: bool,
+}
+
+/// Layout of a JXL file
+#[derive(Clone, Debug)]
+pub struct FileStructure {
+    pub kind: FileKind,
+    pub file_size: u64,
+    pub boxes: Vec<JxlBox>,
+    pub partial_codestreams: Vec<PartialCodestream>,
+    pub codestream_size: u64,
+    pub image_size: Option<(usize, usize)>,
+    pub frames: Vec<FrameInfo>,
+    /// Set when walking the frame headers failed part-way
+    pub frames_error: Option<String>,
+}
+
+/// Inspect the box layout and frame headers of a JXL file on disk
+pub fn inspect_file<P: AsRef<Path>>(path: P) -> Result<FileStructure> {
+    let bytes = std::fs::read(path)?;
+    inspect(&bytes)
+}
+
+/// Inspect the box layout and frame headers of a JXL file
+pub fn inspect(bytes: &[u8]) -> Result<FileStructure> {
+    let kind = container::detect_kind(bytes);
+    let boxes = container::parse_boxes(bytes)?;
+
+    let partial_codestreams = boxes
+        .iter()
+        .filter(|b| &b.box_type == b"jxlp")
+        .filter_map(|b| {
+            let data = b.data(bytes);
+            let raw = u32::from_be_bytes(data.get(..4)?.try_into().ok()?);
+            Some(PartialCodestream {
+                index: raw & 0x7FFF_FFFF,
+                is_last: raw & 0x8000_0000 != 0,
+                offset: b.offset,
+                size: b.size,
+            })
+        })
+        .collect();
+
+    let codestream_size = match kind {
+        FileKind::Codestream => bytes.len() as u64,
+        _ => boxes
+            .iter()
+            .map(|b| match &b.box_type {
+                b"jxlc" => b.payload.len() as u64,
+                b"jxlp" => b.payload.len().saturating_sub(4) as u64,
+                _ => 0,
+            })
+            .sum(),
+    };
+
+    let mut structure = FileStructure {
+        kind,
+        file_size: bytes.len() as u64,
+        boxes,
+        partial_codestreams,
+        codestream_size,
+        image_size: None,
+        frames: Vec::new(),
+        frames_error: None,
+    };
+
+    if let Err(e) = read_frame_headers(bytes, &mut structure) {
+        structure.frames_error = Some(e.to_string());
+    }
+
+    Ok(structure)
+}
+
+/// Walk all frame headers without coalescing, skipping the pixel data
+fn read_frame_headers(bytes: &[u8], structure: &mut FileStructure) -> Result<()> {
+    let mut reader = Cursor::new(bytes);
+
+    let mut options = JxlDecoderOptions::default();
+    options.coalescing = false;
+
+    let mut decoder = match JxlDecoder::new(options).process(&mut reader)? {
+        ProcessingResult::Complete { result } => result,
+        ProcessingResult::NeedsMoreInput { .. } => {
+            anyhow::bail!("Truncated image header");
+        }
+    };
+
+    let image_size = decoder.basic_info().size;
+    let tick_rate = decoder
+        .basic_info()
+        .animation
+        .as_ref()
+        .map(|a| TickRate::new(a.tps_numerator, a.tps_denominator));
+    structure.image_size = Some(image_size);
+
+    while decoder.has_more_frames() {
+        let decoder_with_frame = match decoder.process(&mut reader)? {
+            ProcessingResult::Complete { result } => result,
+            ProcessingResult::NeedsMoreInput { .. } => {
+                anyhow::bail!("Truncated frame header after {} frames", structure.frames.len());
+            }
+        };
+
+        let header = decoder_with_frame.frame_header();
+        structure.frames.push(FrameInfo {
+            name: header.name.clone(),
+            duration: tick_rate.map(|rate| timing::frame_duration(header.duration, rate)),
+            size: header.size,
+            is_cropped: header.size != image_size,
+        });
+
+        decoder = match decoder_with_frame.skip_frame(&mut reader)? {
+            ProcessingResult::Complete { result } => result,
+            ProcessingResult::NeedsMoreInput { .. } => {
+                anyhow::bail!("Truncated frame data in frame {}", structure.frames.len() - 1);
+            }
+        };
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decoder::container::CONTAINER_SIGNATURE;
+
+    const CODESTREAM: &[u8] = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/golden/rgb.jxl"));
+
+    fn make_box(box_type: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+        let mut out = ((payload.len() + 8) as u32).to_be_bytes().to_vec();
+        out.extend_from_slice(box_type);
+        out.extend_from_slice(payload);
+        out
+    }
+
+    fn jxlp(index: u32, part: &[u8]) -> Vec<u8> {
+        let mut payload = index.to_be_bytes().to_vec();
+        payload.extend_from_slice(part);
+        make_box(b"jxlp", &payload)
+    }
+
+    #[test]
+    fn test_split_codestream() {
+        let (first, second) = CODESTREAM.split_at(CODESTREAM.len() / 2);
+        let mut file = CONTAINER_SIGNATURE.to_vec();
+        file.extend(make_box(b"ftyp", b"jxl \0\0\0\0jxl "));
+        file.extend(jxlp(0, first));
+        file.extend(jxlp(0x8000_0001, second));
+
+        let structure = inspect(&file).unwrap();
+        assert_eq!(structure.kind, FileKind::Container);
+        let parts: Vec<(u32, bool, u64)> = structure
+            .partial_codestreams
+            .iter()
+            .map(|p| (p.index, p.is_last, p.size))
+            .collect();
+        assert_eq!(parts, [(0, false, first.len() as u64 + 12), (1, true, second.len() as u64 + 12)]);
+        assert_eq!(structure.partial_codestreams[0].offset, 32);
+
+        // The 4-byte index of each jxlp box is not part of the codestream
+        assert_eq!(structure.codestream_size, CODESTREAM.len() as u64);
+        assert_eq!(structure.frames_error, None);
+        assert_eq!(structure.frames.len(), 1);
+        assert_eq!(structure.image_size, inspect(CODESTREAM).unwrap().image_size);
+    }
+
+    #[test]
+    fn test_truncated_split_codestream() {
+        let (first, _) = CODESTREAM.split_at(CODESTREAM.len() / 2);
+        let mut file = CONTAINER_SIGNATURE.to_vec();
+        file.extend(jxlp(0, first));
+
+        let structure = inspect(&file).unwrap();
+        assert_eq!(structure.codestream_size, first.len() as u64);
+        assert!(structure.frames_error.is_some());
+    }
+}
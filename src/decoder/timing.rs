@@ -0,0 +1,103 @@
+//! Animation timing based on the codestream's tick rate
+//!
+//! JPEG XL frame durations are whole ticks of `tps_denominator / tps_numerator`
+//! seconds. jxl-rs reports them converted to (fractional) milliseconds, so we
+//! snap back to whole ticks and derive exact durations from those.
+
+/// Animation tick rate: `numerator / denominator` ticks per second
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TickRate {
+    pub numerator: u32,
+    pub denominator: u32,
+}
+
+impl TickRate {
+    pub fn new(numerator: u32, denominator: u32) -> Self {
+        Self {
+            numerator: numerator.max(1),
+            denominator: denominator.max(1),
+        }
+    }
+
+    /// Ticks per second as a float, for display
+    pub fn ticks_per_second(&self) -> f64 {
+        self.numerator as f64 / self.denominator as f64
+    }
+
+    /// Length of `ticks` ticks in milliseconds
+    pub fn ticks_to_ms(&self, ticks: u64) -> f64 {
+        ticks as f64 * 1000.0 * self.denominator as f64 / self.numerator as f64
+    }
+
+    /// Nearest whole number of ticks for a duration in milliseconds
+    pub fn ms_to_ticks(&self, ms: f64) -> u64 {
+        (ms * self.numerator as f64 / (1000.0 * self.denominator as f64))
+            .round()
+            .max(0.0) as u64
+    }
+}
+
+/// Duration of one frame, as ticks and derived milliseconds
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FrameDuration {
+    pub ticks: u64,
+    pub ms: f64,
+}
+
+/// Frame duration from the millisecond value jxl-rs reports in the frame header
+pub fn frame_duration(reported_ms: Option<f64>, rate: TickRate) -> FrameDuration {
+    let ticks = rate.ms_to_ticks(reported_ms.unwrap_or(0.0));
+    FrameDuration {
+        ticks,
+        ms: rate.ticks_to_ms(ticks),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_millisecond_ticks() {
+        let rate = TickRate::new(1000, 1);
+        let d = frame_duration(Some(40.0), rate);
+        assert_eq!(d.ticks, 40);
+        assert_eq!(d.ms, 40.0);
+    }
+
+    #[test]
+    fn test_ntsc_tick_rate() {
+        // 30000/1001 ticks per second: one tick is 33.3667 ms
+        let rate = TickRate::new(30000, 1001);
+        let d = frame_duration(Some(1001.0 / 30.0), rate);
+        assert_eq!(d.ticks, 1);
+        assert!((d.ms - 33.366_666).abs() < 1e-5);
+
+        // 30000 frames of one tick each last exactly 1001 seconds, with no drift
+        assert_eq!(rate.ticks_to_ms(30000), 1_001_000.0);
+    }
+
+    #[test]
+    fn test_slow_and_fractional_tick_rates() {
+        // One tick per two seconds
+        let rate = TickRate::new(1, 2);
+        let d = frame_duration(Some(6000.0), rate);
+        assert_eq!(d.ticks, 3);
+        assert_eq!(rate.ticks_to_ms(d.ticks), 6000.0);
+
+        // 7 ticks per 3 seconds: 428.571... ms per tick
+        let rate = TickRate::new(7, 3);
+        let d = frame_duration(Some(3000.0 / 7.0 * 5.0), rate);
+        assert_eq!(d.ticks, 5);
+        assert_eq!(rate.ticks_to_ms(7), 3000.0);
+    }
+
+    #[test]
+    fn test_zero_and_missing_durations() {
+        let rate = TickRate::new(100, 1);
+        assert_eq!(frame_duration(Some(0.0), rate).ticks, 0);
+        assert_eq!(frame_duration(None, rate).ms, 0.0);
+        // Invalid zero rates are clamped rather than dividing by zero
+        assert_eq!(TickRate::new(0, 0), TickRate::new(1, 1));
+    }
+}
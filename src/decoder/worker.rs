@@ -1,56 +1,206 @@
-use super::{DecodeResult, DecodedFrame, ImageMetadata};
+use super::{DecodeResult, DecodedFloatFrame, DecodedFrame, DecodedLayer, ImageMetadata};
 use super::rgb_conversion::jxl_to_rgba8;
+use super::timing::{self, TickRate};
 use anyhow::Result;
 use jxl::api::{
-    states::WithImageInfo,
-    JxlAnimation, JxlBitDepth, JxlColorType, JxlDecoder, JxlDecoderOptions, JxlOutputBuffer, ProcessingResult,
+    states::{WithFrameInfo, WithImageInfo},
+    JxlAnimation, JxlBitDepth, JxlColorType, JxlDecoder, JxlDecoderOptions, JxlOrientation, JxlOutputBuffer,
+    ProcessingResult,
 };
 use jxl::image::{Image, Rect};
 use std::fs::File;
-use std::io::BufReader;
+use std::io::{BufReader, Cursor, Read, Seek};
+use std::fmt;
 use std::panic;
 use std::path::Path;
-use std::time::Instant;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use rayon::prelude::*;
 
 /// Split interleaved RGB channels into separate planar channels
-fn split_rgb_channels(
+///
+/// Pixels missing from a too-small `interleaved` image are left at zero.
+pub fn split_rgb_channels(
     interleaved: &Image<f32>,
     width: usize,
     height: usize,
-) -> (Image<f32>, Image<f32>, Image<f32>) {
-    let mut r = Image::<f32>::new((width, height)).unwrap();
-    let mut g = Image::<f32>::new((width, height)).unwrap();
-    let mut b = Image::<f32>::new((width, height)).unwrap();
-
-    for y in 0..height {
-        let interleaved_row = interleaved.row(y);
-        let r_row = r.row_mut(y);
-        let g_row = g.row_mut(y);
-        let b_row = b.row_mut(y);
-
-        for x in 0..width {
-            let interleaved_idx = x * 3;
-            r_row[x] = interleaved_row[interleaved_idx];
-            g_row[x] = interleaved_row[interleaved_idx + 1];
-            b_row[x] = interleaved_row[interleaved_idx + 2];
+) -> Result<(Image<f32>, Image<f32>, Image<f32>)> {
+    let mut r = Image::<f32>::new((width, height))?;
+    let mut g = Image::<f32>::new((width, height))?;
+    let mut b = Image::<f32>::new((width, height))?;
+
+    let (interleaved_width, interleaved_height) = interleaved.size();
+    let width = width.min(interleaved_width / 3);
+    let height = height.min(interleaved_height);
+    if width == 0 {
+        return Ok((r, g, b));
+    }
+
+    // Deinterleave rows in parallel into a flat buffer, which unlike the images
+    // can be split into rows for each thread, then copy the rows over
+    let rows: Vec<&[f32]> = (0..height).map(|y| &interleaved.row(y)[..width * 3]).collect();
+    let mut planes = vec![0.0f32; width * height * 3];
+    planes.par_chunks_mut(width * 3).zip(rows).for_each(|(out, row)| {
+        let (r_out, rest) = out.split_at_mut(width);
+        let (g_out, b_out) = rest.split_at_mut(width);
+        for (x, px) in row.chunks_exact(3).enumerate() {
+            r_out[x] = px[0];
+            g_out[x] = px[1];
+            b_out[x] = px[2];
         }
+    });
+
+    for (y, row) in planes.chunks_exact(width * 3).enumerate() {
+        r.row_mut(y)[..width].copy_from_slice(&row[..width]);
+        g.row_mut(y)[..width].copy_from_slice(&row[width..width * 2]);
+        b.row_mut(y)[..width].copy_from_slice(&row[width * 2..]);
     }
 
-    (r, g, b)
+    Ok((r, g, b))
+}
+
+/// The decoder panicked instead of returning an error
+///
+/// Kept distinct from ordinary decode errors so a crash in jxl-rs is never
+/// mistaken for a broken or truncated file.
+#[derive(Debug)]
+pub struct DecoderPanic(pub String);
+
+impl fmt::Display for DecoderPanic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Decoder crashed: {}", self.0)
+    }
+}
+
+impl std::error::Error for DecoderPanic {}
+
+/// Run a decode, turning a panic into a [`DecoderPanic`] error
+pub fn catch_panic<T>(f: impl FnOnce() -> Result<T>) -> Result<T> {
+    panic::catch_unwind(panic::AssertUnwindSafe(f)).unwrap_or_else(|payload| {
+        let message = payload
+            .downcast_ref::<&str>()
+            .map(|s| s.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown panic".to_string());
+        log::warn!("Decoder panicked: {}", message);
+        Err(DecoderPanic(message).into())
+    })
+}
+
+/// Refuse images with more than `limit` pixels before any pixel buffers are allocated
+pub fn check_pixel_limit(size: (usize, usize), limit: Option<usize>) -> Result<()> {
+    let (width, height) = size;
+    match limit {
+        Some(limit) if width * height > limit => anyhow::bail!(
+            "Image is {}×{} ({:.1} MP), above the {:.0} MP pixel limit. Raise the limit in Settings to open it.",
+            width,
+            height,
+            (width * height) as f64 / 1_000_000.0,
+            limit as f64 / 1_000_000.0
+        ),
+        _ => Ok(()),
+    }
+}
+
+/// Refuse an animation once `frame_count` frames of `size` would take more
+/// than `limit` bytes as RGBA8
+///
+/// Coalesced frames can only be decoded in order from the start, so frames
+/// cannot be dropped and decoded again later; the whole animation has to fit.
+pub fn check_memory_limit(size: (usize, usize), frame_count: usize, limit: Option<usize>) -> Result<()> {
+    let bytes = size.0 * size.1 * 4 * frame_count;
+    match limit {
+        Some(limit) if bytes > limit => anyhow::bail!(
+            "Animation frames do not fit in the {:.0} MB memory budget ({}×{}, more than {} frames). Raise the budget in Settings to open it.",
+            limit as f64 / 1_048_576.0,
+            size.0,
+            size.1,
+            frame_count.saturating_sub(1)
+        ),
+        _ => Ok(()),
+    }
+}
+
+/// Decoder settings chosen by the caller
+#[derive(Clone, Debug)]
+pub struct DecodeOptions {
+    /// Refuse images with more pixels than this
+    pub pixel_limit: Option<usize>,
+    /// Apply the orientation from the image header; when off, pixels are
+    /// returned as stored in the codestream
+    pub adjust_orientation: bool,
+    /// Refuse animations whose RGBA8 frames take more bytes than this
+    pub memory_limit: Option<usize>,
+    /// Threads for splitting channels and converting to RGBA; `None` uses
+    /// one per core. jxl-rs itself decodes on a single thread.
+    pub threads: Option<usize>,
+}
+
+impl Default for DecodeOptions {
+    fn default() -> Self {
+        Self {
+            pixel_limit: None,
+            memory_limit: None,
+            adjust_orientation: true,
+            threads: None,
+        }
+    }
 }
 
 /// Unified decode function that automatically handles both single frames and animations
-pub fn decode_jxl<P: AsRef<Path>>(path: P) -> Result<DecodeResult> {
+///
+/// Images larger than `options.pixel_limit` pixels are refused with an error.
+pub fn decode_jxl<P: AsRef<Path>>(path: P, options: &DecodeOptions) -> Result<DecodeResult> {
     let start = Instant::now();
 
     log::info!("Opening JXL file: {:?}", path.as_ref());
 
     let file = File::open(path.as_ref())?;
-    let mut reader = BufReader::new(file);
+    in_thread_pool(options.threads, || decode_jxl_from(BufReader::new(file), options, start))
+}
+
+/// [`decode_jxl`] for a file already in memory
+pub fn decode_jxl_bytes(data: &[u8], options: &DecodeOptions) -> Result<DecodeResult> {
+    in_thread_pool(options.threads, || {
+        decode_jxl_from(BufReader::new(Cursor::new(data)), options, Instant::now())
+    })
+}
 
+/// Run `f` on a pool of `threads` threads, or on rayon's global pool with one
+/// thread per core
+///
+/// jxl-rs decodes on the calling thread and has no thread setting of its own,
+/// so the pool only bounds the parallel channel splitting and RGBA conversion.
+/// One pool is shared by all decodes and rebuilt when the thread count
+/// changes; decodes still running keep the old pool until they finish.
+fn in_thread_pool<T: Send>(threads: Option<usize>, f: impl FnOnce() -> Result<T> + Send) -> Result<T> {
+    static POOL: Mutex<Option<(usize, Arc<rayon::ThreadPool>)>> = Mutex::new(None);
+
+    let Some(threads) = threads else {
+        return f();
+    };
+    let pool = {
+        let mut pool = POOL.lock().unwrap_or_else(|e| e.into_inner());
+        match &*pool {
+            Some((count, current)) if *count == threads => current.clone(),
+            _ => {
+                let new = Arc::new(rayon::ThreadPoolBuilder::new().num_threads(threads).build()?);
+                *pool = Some((threads, new.clone()));
+                new
+            }
+        }
+    };
+    pool.install(f)
+}
+
+fn decode_jxl_from<R: Read + Seek>(
+    mut reader: BufReader<R>,
+    decode_options: &DecodeOptions,
+    start: Instant,
+) -> Result<DecodeResult> {
     // Set up decoder options
     let mut options = JxlDecoderOptions::default();
-    options.adjust_orientation = true;
+    options.adjust_orientation = decode_options.adjust_orientation;
     options.coalescing = true; // Blend frames for animation
     options.premultiply_output = true; // Premultiply alpha for better compositing
 
@@ -67,9 +217,11 @@ pub fn decode_jxl<P: AsRef<Path>>(path: P) -> Result<DecodeResult> {
 
     let basic_info = decoder_with_info.basic_info();
     let (width, height) = basic_info.size;
+    check_pixel_limit(basic_info.size, decode_options.pixel_limit)?;
     let extra_channels_count = basic_info.extra_channels.len();
     let bit_depth = basic_info.bit_depth.clone();
     let animation = basic_info.animation.clone();
+    let orientation = exif_orientation(basic_info.orientation);
 
     // Get the color type from the decoder's pixel format
     let pixel_format = decoder_with_info.current_pixel_format();
@@ -97,6 +249,7 @@ pub fn decode_jxl<P: AsRef<Path>>(path: P) -> Result<DecodeResult> {
             extra_channels_count,
             &bit_depth,
             &animation,
+            orientation,
             start,
         )?;
 
@@ -109,125 +262,54 @@ pub fn decode_jxl<P: AsRef<Path>>(path: P) -> Result<DecodeResult> {
     let mut frames = Vec::new();
     let mut decoder = decoder_with_info;
     let mut frame_index = 0;
+    let tick_rate = tick_rate_of(&animation);
 
-    loop {
+    while decoder.has_more_frames() {
         let frame_start = Instant::now();
+        check_memory_limit((width, height), frames.len() + 1, decode_options.memory_limit)?;
 
-        // Get frame info - catch panic if we've reached the end of frames
-        // The JXL decoder panics with "assertion failed: self.has_more_frames"
-        // when trying to decode beyond the last frame
-        let decoder_with_frame = match panic::catch_unwind(panic::AssertUnwindSafe(|| decoder.process(&mut reader))) {
-            Ok(Ok(ProcessingResult::Complete { result })) => result,
-            Ok(Ok(ProcessingResult::NeedsMoreInput { .. })) => {
-                // No more frames
-                log::info!("Decoded {} frames total", frames.len());
-                break;
-            }
-            Ok(Err(e)) => {
-                // Decoding error
-                return Err(e.into());
-            }
-            Err(_) => {
-                // Panic caught - likely end of animation
-                log::info!("Decoded {} frames total (end of animation detected)", frames.len());
-                break;
+        let decoder_with_frame = match decoder.process(&mut reader)? {
+            ProcessingResult::Complete { result } => result,
+            ProcessingResult::NeedsMoreInput { .. } => {
+                anyhow::bail!("Truncated animation: file ends after {} frames", frames.len());
             }
         };
 
+        // jxl-rs reports the duration in milliseconds; snap it back to whole ticks.
+        // Zero-duration frames are kept as-is; playback skips over them.
         let frame_header = decoder_with_frame.frame_header();
-        let raw_duration = frame_header.duration.unwrap_or(100.0); // Raw duration value from JXL
-
-        // It appears jxl-rs returns duration already in milliseconds
-        // Just need to ensure it's reasonable and convert to u32
-        let duration_ms = raw_duration as u32;
-        let duration_ms = duration_ms.max(16); // Minimum 16ms (60fps)
-
-        log::info!("Decoding frame {} (duration: {}ms)...", frame_index, duration_ms);
-
-        // Determine samples per pixel
-        let samples_per_pixel = match color_type {
-            JxlColorType::Grayscale => 1,
-            JxlColorType::GrayscaleAlpha => 1,
-            JxlColorType::Rgb | JxlColorType::Bgr => 3,
-            JxlColorType::Rgba | JxlColorType::Bgra => 3,
-        };
+        let duration = timing::frame_duration(frame_header.duration, tick_rate);
 
-        // Create output buffers
-        let mut main_channel = Image::<f32>::new((width * samples_per_pixel, height))?;
-        let mut extra_channel_buffers: Vec<Image<f32>> = (0..extra_channels_count)
-            .map(|_| Image::<f32>::new((width, height)))
-            .collect::<Result<Vec<_>, _>>()?;
-
-        let rect = Rect {
-            size: main_channel.size(),
-            origin: (0, 0),
-        };
-
-        let mut output_bufs = vec![JxlOutputBuffer::from_image_rect_mut(
-            main_channel.get_rect_mut(rect).into_raw(),
-        )];
-        for extra in &mut extra_channel_buffers {
-            let extra_rect = Rect {
-                size: extra.size(),
-                origin: (0, 0),
-            };
-            output_bufs.push(JxlOutputBuffer::from_image_rect_mut(
-                extra.get_rect_mut(extra_rect).into_raw(),
-            ));
-        }
+        log::info!(
+            "Decoding frame {} (duration: {} ticks, {:.3}ms)...",
+            frame_index,
+            duration.ticks,
+            duration.ms
+        );
 
         // Decode the frame
-        decoder = match decoder_with_frame.process(&mut reader, &mut output_bufs)? {
-            ProcessingResult::Complete { result } => result,
-            ProcessingResult::NeedsMoreInput { .. } => {
-                anyhow::bail!("Unexpected NeedsMoreInput during frame decode");
-            }
-        };
+        let (next_decoder, channels) = decode_frame_channels(
+            decoder_with_frame,
+            &mut reader,
+            width,
+            height,
+            color_type,
+            extra_channels_count,
+        )?;
+        decoder = next_decoder;
 
         let decode_time = frame_start.elapsed();
 
-        // Prepare channels for RGB conversion
-        let mut channels = Vec::new();
-        match color_type {
-            JxlColorType::Grayscale => {
-                channels.push(main_channel);
-            }
-            JxlColorType::GrayscaleAlpha => {
-                channels.push(main_channel);
-                if !extra_channel_buffers.is_empty() {
-                    channels.push(extra_channel_buffers.remove(0));
-                }
-            }
-            JxlColorType::Rgb | JxlColorType::Bgr => {
-                let (r, g, b) = split_rgb_channels(&main_channel, width, height);
-                channels.push(r);
-                channels.push(g);
-                channels.push(b);
-                // Add alpha channel if present as extra channel
-                if !extra_channel_buffers.is_empty() {
-                    channels.push(extra_channel_buffers.remove(0));
-                }
-            }
-            JxlColorType::Rgba | JxlColorType::Bgra => {
-                let (r, g, b) = split_rgb_channels(&main_channel, width, height);
-                channels.push(r);
-                channels.push(g);
-                channels.push(b);
-                if !extra_channel_buffers.is_empty() {
-                    channels.push(extra_channel_buffers.remove(0));
-                }
-            }
-        }
-
         // Convert to RGBA8
-        let rgba_data = jxl_to_rgba8(&channels, color_type, width, height);
+        let (rgba_data, conversion_time) = convert_to_rgba8(&channels, color_type, width, height);
 
         let frame = DecodedFrame {
             rgba_data,
             width: width as u32,
             height: height as u32,
             decode_time,
-            duration_ms,
+            conversion_time,
+            duration_ms: duration.ms,
         };
 
         frames.push(frame);
@@ -237,6 +319,10 @@ pub fn decode_jxl<P: AsRef<Path>>(path: P) -> Result<DecodeResult> {
     let total_time = start.elapsed();
     log::info!("Decoded all {} frames in {:?}", frames.len(), total_time);
 
+    if frames.is_empty() {
+        anyhow::bail!("Animation contains no frames");
+    }
+
     // Create metadata
     let metadata = ImageMetadata {
         width: width as u32,
@@ -245,21 +331,345 @@ pub fn decode_jxl<P: AsRef<Path>>(path: P) -> Result<DecodeResult> {
         has_animation: true,
         frame_count: frames.len(),
         loop_count: animation.as_ref().map(|a| a.num_loops).unwrap_or(0),
+        tick_rate: Some(tick_rate),
+        have_timecodes: animation.as_ref().is_some_and(|a| a.have_timecodes),
+        orientation,
     };
 
     Ok(DecodeResult::Animation { frames, metadata })
 }
 
+/// Images with more pixels than this show their embedded preview, if they have
+/// one, while decoding
+pub const PREVIEW_MIN_PIXELS: usize = 16_000_000;
+
+/// Decode the preview frame embedded in a large image, along with the full image size
+///
+/// jxl-rs can neither decode a region of the main image nor decode it at reduced
+/// resolution, so the codestream's preview frame is the only cheap stand-in while
+/// a huge image decodes in full. Returns `None` for small images and images
+/// without a preview.
+pub fn decode_preview<P: AsRef<Path>>(
+    path: P,
+    decode_options: &DecodeOptions,
+) -> Result<Option<(DecodedFrame, (usize, usize))>> {
+    let path = path.as_ref();
+    in_thread_pool(decode_options.threads, || {
+        decode_preview_if(path, decode_options, |image_size, _| {
+            image_size.0 * image_size.1 >= PREVIEW_MIN_PIXELS
+        })
+    })
+}
+
+/// Decode a small version of an image, at most `max_size` pixels on its longer side
+///
+/// The embedded preview is used when it is at least that large, or when the
+/// image is above `decode_options.pixel_limit`. Otherwise the first frame is
+/// decoded in full and scaled down, which fails for images above the limit.
+pub fn decode_thumbnail<P: AsRef<Path>>(
+    path: P,
+    max_size: u32,
+    decode_options: &DecodeOptions,
+) -> Result<DecodedFrame> {
+    let path = path.as_ref();
+    in_thread_pool(decode_options.threads, || decode_thumbnail_from(path, max_size, decode_options))
+}
+
+fn decode_thumbnail_from(path: &Path, max_size: u32, decode_options: &DecodeOptions) -> Result<DecodedFrame> {
+    let start = Instant::now();
+    let preview = decode_preview_if(path, decode_options, |image_size, preview_size| {
+        preview_size.0.max(preview_size.1) >= max_size as usize
+            || check_pixel_limit(image_size, decode_options.pixel_limit).is_err()
+    })?;
+    if let Some((frame, _)) = preview {
+        return Ok(scale_to_fit(frame, max_size));
+    }
+
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut options = JxlDecoderOptions::default();
+    options.adjust_orientation = decode_options.adjust_orientation;
+    options.premultiply_output = true;
+
+    let decoder = match JxlDecoder::new(options).process(&mut reader)? {
+        ProcessingResult::Complete { result } => result,
+        ProcessingResult::NeedsMoreInput { .. } => {
+            anyhow::bail!("Unexpected NeedsMoreInput during header decode");
+        }
+    };
+    let basic_info = decoder.basic_info();
+    let (width, height) = basic_info.size;
+    check_pixel_limit(basic_info.size, decode_options.pixel_limit)?;
+    let extra_channels_count = basic_info.extra_channels.len();
+    let bit_depth = basic_info.bit_depth.clone();
+    let animation = basic_info.animation.clone();
+    let orientation = exif_orientation(basic_info.orientation);
+    let color_type = decoder.current_pixel_format().color_type;
+
+    // Animations are represented by their first frame
+    let (frame, _) = decode_single_frame_from_decoder(
+        decoder,
+        &mut reader,
+        width,
+        height,
+        color_type,
+        extra_channels_count,
+        &bit_depth,
+        &animation,
+        orientation,
+        start,
+    )?;
+    Ok(scale_to_fit(frame, max_size))
+}
+
+/// Shrink a frame to at most `max_size` pixels on its longer side, keeping its aspect ratio
+fn scale_to_fit(frame: DecodedFrame, max_size: u32) -> DecodedFrame {
+    let (width, height) = (frame.width, frame.height);
+    if width.max(height) <= max_size || frame.rgba_data.len() != width as usize * height as usize * 4 {
+        return frame;
+    }
+    let scale = max_size as f64 / width.max(height) as f64;
+    let new_width = ((width as f64 * scale).round() as u32).max(1);
+    let new_height = ((height as f64 * scale).round() as u32).max(1);
+
+    // Averaging premultiplied samples keeps transparent pixels from bleeding their color
+    let image = image::RgbaImage::from_raw(width, height, frame.rgba_data).expect("size checked above");
+    let scaled = image::imageops::thumbnail(&image, new_width, new_height);
+    DecodedFrame {
+        rgba_data: scaled.into_raw(),
+        width: new_width,
+        height: new_height,
+        ..frame
+    }
+}
+
+/// Decode the preview frame if `wanted(image_size, preview_size)` agrees
+fn decode_preview_if(
+    path: &Path,
+    decode_options: &DecodeOptions,
+    wanted: impl Fn((usize, usize), (usize, usize)) -> bool,
+) -> Result<Option<(DecodedFrame, (usize, usize))>> {
+    let start = Instant::now();
+    let file = File::open(path)?;
+    let mut reader = BufReader::new(file);
+
+    let mut options = JxlDecoderOptions::default();
+    options.adjust_orientation = decode_options.adjust_orientation;
+    options.premultiply_output = true;
+    options.skip_preview = false; // The preview comes first, as its own frame
+
+    let decoder = match JxlDecoder::new(options).process(&mut reader)? {
+        ProcessingResult::Complete { result } => result,
+        ProcessingResult::NeedsMoreInput { .. } => {
+            anyhow::bail!("Unexpected NeedsMoreInput during header decode");
+        }
+    };
+
+    let basic_info = decoder.basic_info();
+    let image_size = basic_info.size;
+    let Some(preview_size) = basic_info.preview_size else {
+        return Ok(None);
+    };
+    if !wanted(image_size, preview_size) {
+        return Ok(None);
+    }
+    let extra_channels_count = basic_info.extra_channels.len();
+    let color_type = decoder.current_pixel_format().color_type;
+
+    let decoder_with_frame = match decoder.process(&mut reader)? {
+        ProcessingResult::Complete { result } => result,
+        ProcessingResult::NeedsMoreInput { .. } => {
+            anyhow::bail!("Unexpected NeedsMoreInput during preview header decode");
+        }
+    };
+
+    // The preview size is stored before orientation is applied
+    let (width, height) = decoder_with_frame.frame_header().size;
+    if (width, height) != preview_size && (height, width) != preview_size {
+        return Ok(None);
+    }
+
+    log::info!("Decoding {}x{} preview of {}x{} image...", width, height, image_size.0, image_size.1);
+    let (_, channels) = decode_frame_channels(
+        decoder_with_frame,
+        &mut reader,
+        width,
+        height,
+        color_type,
+        extra_channels_count,
+    )?;
+
+    let decode_time = start.elapsed();
+    let (rgba_data, conversion_time) = convert_to_rgba8(&channels, color_type, width, height);
+    let frame = DecodedFrame {
+        rgba_data,
+        width: width as u32,
+        height: height as u32,
+        decode_time,
+        conversion_time,
+        duration_ms: 0.0,
+    };
+
+    Ok(Some((frame, image_size)))
+}
+
+/// Decode every frame as a separate layer, without coalescing
+///
+/// jxl-rs does not expose the crop offset or blend mode of a frame, so layers
+/// smaller than the canvas are flagged as cropped but cannot be positioned.
+pub fn decode_layers<P: AsRef<Path>>(path: P, decode_options: &DecodeOptions) -> Result<Vec<DecodedLayer>> {
+    let path = path.as_ref();
+    in_thread_pool(decode_options.threads, || decode_layers_from(path, decode_options))
+}
+
+fn decode_layers_from(path: &Path, decode_options: &DecodeOptions) -> Result<Vec<DecodedLayer>> {
+    log::info!("Decoding layers of {:?}", path);
+
+    let file = File::open(path)?;
+    let mut reader = BufReader::new(file);
+
+    let mut options = JxlDecoderOptions::default();
+    options.adjust_orientation = decode_options.adjust_orientation;
+    options.coalescing = false; // Keep layers separate
+    options.premultiply_output = false; // Straight alpha for compositing layers
+
+    let mut decoder = match JxlDecoder::new(options).process(&mut reader)? {
+        ProcessingResult::Complete { result } => result,
+        ProcessingResult::NeedsMoreInput { .. } => {
+            anyhow::bail!("Unexpected NeedsMoreInput during header decode");
+        }
+    };
+
+    let image_size = decoder.basic_info().size;
+    check_pixel_limit(image_size, decode_options.pixel_limit)?;
+    let extra_channels_count = decoder.basic_info().extra_channels.len();
+    let tick_rate = tick_rate_of(&decoder.basic_info().animation);
+    let color_type = decoder.current_pixel_format().color_type;
+
+    let mut layers = Vec::new();
+    while decoder.has_more_frames() {
+        let frame_start = Instant::now();
+
+        let decoder_with_frame = match decoder.process(&mut reader)? {
+            ProcessingResult::Complete { result } => result,
+            ProcessingResult::NeedsMoreInput { .. } => {
+                anyhow::bail!("Unexpected NeedsMoreInput during frame header decode");
+            }
+        };
+
+        let frame_header = decoder_with_frame.frame_header();
+        let (width, height) = frame_header.size;
+        // Layers are not clipped to the canvas, so check each one as well
+        check_pixel_limit(frame_header.size, decode_options.pixel_limit)?;
+        log::info!("Decoding layer {} '{}' ({}x{})...", layers.len(), frame_header.name, width, height);
+
+        let (next_decoder, channels) = decode_frame_channels(
+            decoder_with_frame,
+            &mut reader,
+            width,
+            height,
+            color_type,
+            extra_channels_count,
+        )?;
+        decoder = next_decoder;
+
+        let decode_time = frame_start.elapsed();
+        let (rgba_data, conversion_time) = convert_to_rgba8(&channels, color_type, width, height);
+
+        let duration = timing::frame_duration(frame_header.duration, tick_rate);
+        layers.push(DecodedLayer {
+            name: frame_header.name,
+            is_cropped: frame_header.size != image_size,
+            frame: DecodedFrame {
+                rgba_data,
+                width: width as u32,
+                height: height as u32,
+                decode_time,
+                conversion_time,
+                duration_ms: duration.ms,
+            },
+        });
+    }
+
+    log::info!("Decoded {} layers", layers.len());
+    Ok(layers)
+}
+
+/// Decode every (coalesced) frame at full precision, for comparing against references
+pub fn decode_float_frames<P: AsRef<Path>>(path: P, decode_options: &DecodeOptions) -> Result<Vec<DecodedFloatFrame>> {
+    let path = path.as_ref();
+    in_thread_pool(decode_options.threads, || decode_float_frames_from(path, decode_options))
+}
+
+fn decode_float_frames_from(path: &Path, decode_options: &DecodeOptions) -> Result<Vec<DecodedFloatFrame>> {
+    let file = File::open(path)?;
+    let mut reader = BufReader::new(file);
+
+    let mut options = JxlDecoderOptions::default();
+    options.adjust_orientation = decode_options.adjust_orientation;
+    options.premultiply_output = false;
+
+    let mut decoder = match JxlDecoder::new(options).process(&mut reader)? {
+        ProcessingResult::Complete { result } => result,
+        ProcessingResult::NeedsMoreInput { .. } => {
+            anyhow::bail!("Unexpected NeedsMoreInput during header decode");
+        }
+    };
+
+    let (width, height) = decoder.basic_info().size;
+    check_pixel_limit((width, height), decode_options.pixel_limit)?;
+    let extra_channels_count = decoder.basic_info().extra_channels.len();
+    let color_type = decoder.current_pixel_format().color_type;
+
+    let mut frames = Vec::new();
+    while decoder.has_more_frames() {
+        let decoder_with_frame = match decoder.process(&mut reader)? {
+            ProcessingResult::Complete { result } => result,
+            ProcessingResult::NeedsMoreInput { .. } => {
+                anyhow::bail!("Truncated file: ends after {} frames", frames.len());
+            }
+        };
+
+        let (next_decoder, planes) = decode_frame_channels(
+            decoder_with_frame,
+            &mut reader,
+            width,
+            height,
+            color_type,
+            extra_channels_count,
+        )?;
+        decoder = next_decoder;
+
+        let channels = planes.len();
+        let mut samples = Vec::with_capacity(width * height * channels);
+        for y in 0..height {
+            let rows: Vec<&[f32]> = planes.iter().map(|p| p.row(y)).collect();
+            for x in 0..width {
+                samples.extend(rows.iter().map(|row| row[x]));
+            }
+        }
+
+        frames.push(DecodedFloatFrame {
+            width,
+            height,
+            channels,
+            samples,
+        });
+    }
+
+    Ok(frames)
+}
+
 /// Helper function to decode a single frame from an existing decoder
-fn decode_single_frame_from_decoder(
+fn decode_single_frame_from_decoder<R: Read + Seek>(
     decoder_with_info: JxlDecoder<WithImageInfo>,
-    reader: &mut BufReader<File>,
+    reader: &mut BufReader<R>,
     width: usize,
     height: usize,
     color_type: JxlColorType,
     extra_channels_count: usize,
     bit_depth: &JxlBitDepth,
     animation: &Option<JxlAnimation>,
+    orientation: u8,
     start: Instant,
 ) -> Result<(DecodedFrame, ImageMetadata)> {
     // Get frame info
@@ -272,6 +682,58 @@ fn decode_single_frame_from_decoder(
 
     log::info!("Color type: {:?}, extra channels: {}", color_type, extra_channels_count);
 
+    // Decode the frame
+    log::info!("Decoding frame...");
+    let (_decoder_with_info, channels) = decode_frame_channels(
+        decoder_with_frame,
+        reader,
+        width,
+        height,
+        color_type,
+        extra_channels_count,
+    )?;
+
+    let decode_time = start.elapsed();
+    log::info!("Decoded in {:?}", decode_time);
+
+    // Convert to RGBA8
+    let (rgba_data, conversion_time) = convert_to_rgba8(&channels, color_type, width, height);
+
+    // Create metadata
+    let metadata = ImageMetadata {
+        width: width as u32,
+        height: height as u32,
+        bit_depth: format_bit_depth(bit_depth),
+        has_animation: animation.is_some(),
+        frame_count: 1,
+        loop_count: animation.as_ref().map(|a| a.num_loops).unwrap_or(0),
+        tick_rate: animation.as_ref().map(|_| tick_rate_of(animation)),
+        have_timecodes: animation.as_ref().is_some_and(|a| a.have_timecodes),
+        orientation,
+    };
+
+    let frame = DecodedFrame {
+        rgba_data,
+        width: width as u32,
+        height: height as u32,
+        decode_time,
+        conversion_time,
+        duration_ms: 0.0,
+    };
+
+    Ok((frame, metadata))
+}
+
+/// Decode the pixels of the current frame and split them into planar channels
+/// ready for `jxl_to_rgba8`
+fn decode_frame_channels<R: Read + Seek>(
+    decoder_with_frame: JxlDecoder<WithFrameInfo>,
+    reader: &mut BufReader<R>,
+    width: usize,
+    height: usize,
+    color_type: JxlColorType,
+    extra_channels_count: usize,
+) -> Result<(JxlDecoder<WithImageInfo>, Vec<Image<f32>>)> {
     // Determine samples per pixel
     let samples_per_pixel = match color_type {
         JxlColorType::Grayscale => 1,
@@ -305,17 +767,13 @@ fn decode_single_frame_from_decoder(
     }
 
     // Decode the frame
-    log::info!("Decoding frame...");
-    let _decoder_with_info = match decoder_with_frame.process(reader, &mut output_bufs)? {
+    let decoder = match decoder_with_frame.process(reader, &mut output_bufs)? {
         ProcessingResult::Complete { result } => result,
         ProcessingResult::NeedsMoreInput { .. } => {
             anyhow::bail!("Unexpected NeedsMoreInput during frame decode");
         }
     };
 
-    let decode_time = start.elapsed();
-    log::info!("Decoded in {:?}", decode_time);
-
     // Prepare channels for RGB conversion
     let mut channels = Vec::new();
     match color_type {
@@ -329,7 +787,7 @@ fn decode_single_frame_from_decoder(
             }
         }
         JxlColorType::Rgb | JxlColorType::Bgr => {
-            let (r, g, b) = split_rgb_channels(&main_channel, width, height);
+            let (r, g, b) = split_rgb_channels(&main_channel, width, height)?;
             channels.push(r);
             channels.push(g);
             channels.push(b);
@@ -339,7 +797,7 @@ fn decode_single_frame_from_decoder(
             }
         }
         JxlColorType::Rgba | JxlColorType::Bgra => {
-            let (r, g, b) = split_rgb_channels(&main_channel, width, height);
+            let (r, g, b) = split_rgb_channels(&main_channel, width, height)?;
             channels.push(r);
             channels.push(g);
             channels.push(b);
@@ -349,28 +807,41 @@ fn decode_single_frame_from_decoder(
         }
     }
 
-    // Convert to RGBA8
-    let rgba_data = jxl_to_rgba8(&channels, color_type, width, height);
+    Ok((decoder, channels))
+}
 
-    // Create metadata
-    let metadata = ImageMetadata {
-        width: width as u32,
-        height: height as u32,
-        bit_depth: format_bit_depth(bit_depth),
-        has_animation: animation.is_some(),
-        frame_count: 1,
-        loop_count: animation.as_ref().map(|a| a.num_loops).unwrap_or(0),
-    };
+/// Convert decoded channels to RGBA8 with [`jxl_to_rgba8`], along with the time it took
+fn convert_to_rgba8(
+    channels: &[Image<f32>],
+    color_type: JxlColorType,
+    width: usize,
+    height: usize,
+) -> (Vec<u8>, Duration) {
+    let start = Instant::now();
+    let rgba = jxl_to_rgba8(channels, color_type, width, height);
+    (rgba, start.elapsed())
+}
 
-    let frame = DecodedFrame {
-        rgba_data,
-        width: width as u32,
-        height: height as u32,
-        decode_time,
-        duration_ms: 0,
-    };
+/// Tick rate of an animation, defaulting to milliseconds for still images
+fn tick_rate_of(animation: &Option<JxlAnimation>) -> TickRate {
+    animation
+        .as_ref()
+        .map(|a| TickRate::new(a.tps_numerator, a.tps_denominator))
+        .unwrap_or(TickRate::new(1000, 1))
+}
 
-    Ok((frame, metadata))
+/// EXIF orientation value (1-8) of the image header's orientation
+fn exif_orientation(orientation: JxlOrientation) -> u8 {
+    match orientation {
+        JxlOrientation::Identity => 1,
+        JxlOrientation::FlipHorizontal => 2,
+        JxlOrientation::Rotate180 => 3,
+        JxlOrientation::FlipVertical => 4,
+        JxlOrientation::Transpose => 5,
+        JxlOrientation::Rotate90Cw => 6,
+        JxlOrientation::AntiTranspose => 7,
+        JxlOrientation::Rotate90Ccw => 8,
+    }
 }
 
 /// Format bit depth for display
@@ -386,3 +857,195 @@ fn format_bit_depth(bit_depth: &JxlBitDepth) -> String {
         ),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decoder::container::{CODESTREAM_SIGNATURE, CONTAINER_SIGNATURE};
+    use std::path::PathBuf;
+
+    /// Animated files to truncate, generated by `tests/fixtures/generate.py`
+    const ANIMATION_CORPUS: &str = "tests/fixtures/animations";
+
+    fn write_temp(name: &str, bytes: &[u8]) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("jxl-ui-{}-{}.jxl", std::process::id(), name));
+        std::fs::write(&path, bytes).unwrap();
+        path
+    }
+
+    /// Decode `bytes`, asserting that the decoder does not panic
+    fn decode_without_panic(name: &str, bytes: &[u8]) -> Result<DecodeResult> {
+        let path = write_temp(name, bytes);
+        let result = catch_panic(|| decode_jxl(&path, &DecodeOptions::default()));
+        std::fs::remove_file(&path).ok();
+
+        if let Err(e) = &result {
+            assert!(e.downcast_ref::<DecoderPanic>().is_none(), "{}: {}", name, e);
+        }
+        result
+    }
+
+    fn assert_clean_error(name: &str, bytes: &[u8]) {
+        assert!(decode_without_panic(name, bytes).is_err(), "{}: decoded successfully", name);
+    }
+
+    #[test]
+    fn test_catch_panic() {
+        let err = catch_panic::<()>(|| panic!("boom")).unwrap_err();
+        let panic = err.downcast_ref::<DecoderPanic>().unwrap();
+        assert_eq!(panic.0, "boom");
+        assert_eq!(err.to_string(), "Decoder crashed: boom");
+
+        let err = catch_panic::<()>(|| anyhow::bail!("bad file")).unwrap_err();
+        assert!(err.downcast_ref::<DecoderPanic>().is_none());
+    }
+
+    #[test]
+    fn test_pixel_limit() {
+        assert!(check_pixel_limit((4000, 3000), None).is_ok());
+        assert!(check_pixel_limit((4000, 3000), Some(12_000_000)).is_ok());
+
+        let err = check_pixel_limit((4000, 3001), Some(12_000_000)).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Image is 4000×3001 (12.0 MP), above the 12 MP pixel limit. Raise the limit in Settings to open it."
+        );
+    }
+
+    #[test]
+    fn test_memory_limit() {
+        assert!(check_memory_limit((500, 500), 3, None).is_ok());
+        assert!(check_memory_limit((500, 500), 2, Some(2 * 1_048_576)).is_ok());
+
+        let err = check_memory_limit((500, 500), 3, Some(2 * 1_048_576)).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Animation frames do not fit in the 2 MB memory budget (500×500, more than 2 frames). Raise the budget in Settings to open it."
+        );
+
+        // The three 45x30 frames of the fixture need 16200 bytes
+        let path = Path::new(ANIMATION_CORPUS).join("animated.jxl");
+        let limited = |memory_limit| DecodeOptions { memory_limit, ..DecodeOptions::default() };
+        assert!(decode_jxl(&path, &limited(Some(16_200))).is_ok());
+        assert!(decode_jxl(&path, &limited(Some(16_199))).is_err());
+    }
+
+    #[test]
+    fn test_split_short_rgb() {
+        // An interleaved buffer narrower than the image leaves the missing pixels at zero
+        let mut interleaved = Image::<f32>::new((3, 1)).unwrap();
+        interleaved.row_mut(0).copy_from_slice(&[0.25, 0.5, 0.75]);
+        let (r, g, b) = split_rgb_channels(&interleaved, 2, 2).unwrap();
+        assert_eq!(r.size(), (2, 2));
+        assert_eq!((r.row(0), g.row(0), b.row(0)), (&[0.25, 0.0][..], &[0.5, 0.0][..], &[0.75, 0.0][..]));
+        assert_eq!(b.row(1), &[0.0, 0.0]);
+    }
+
+    #[test]
+    fn test_scale_to_fit() {
+        let frame = |width: u32, height: u32| DecodedFrame {
+            rgba_data: vec![200; (width * height * 4) as usize],
+            width,
+            height,
+            decode_time: Duration::ZERO,
+            conversion_time: Duration::ZERO,
+            duration_ms: 0.0,
+        };
+
+        let scaled = scale_to_fit(frame(400, 100), 128);
+        assert_eq!((scaled.width, scaled.height), (128, 32));
+        assert_eq!(scaled.rgba_data.len(), 128 * 32 * 4);
+        assert!(scaled.rgba_data.iter().all(|&v| v == 200));
+
+        // Tall images keep at least one pixel across
+        let scaled = scale_to_fit(frame(1, 1000), 100);
+        assert_eq!((scaled.width, scaled.height), (1, 100));
+
+        let small = scale_to_fit(frame(64, 48), 128);
+        assert_eq!((small.width, small.height), (64, 48));
+    }
+
+    #[test]
+    fn test_thumbnail_pixel_limit() {
+        // Without an embedded preview, images above the limit are not decoded for a thumbnail
+        let path = Path::new("tests/fixtures/golden/rgb.jxl");
+        let limited = |pixel_limit| DecodeOptions { pixel_limit, threads: Some(1), ..DecodeOptions::default() };
+        let err = decode_thumbnail(path, 16, &limited(Some(1))).unwrap_err();
+        assert!(err.to_string().contains("pixel limit"), "{}", err);
+
+        let frame = decode_thumbnail(path, 16, &limited(Some(100_000_000))).unwrap();
+        assert!(frame.width.max(frame.height) <= 16);
+    }
+
+    #[test]
+    fn test_truncated_headers() {
+        assert_clean_error("empty", &[]);
+        assert_clean_error("signature", &CODESTREAM_SIGNATURE);
+        assert_clean_error("container", &CONTAINER_SIGNATURE);
+
+        let mut partial_box = CONTAINER_SIGNATURE.to_vec();
+        partial_box.extend_from_slice(&[0, 0, 1, 0]);
+        partial_box.extend_from_slice(b"jxlc");
+        partial_box.extend_from_slice(&CODESTREAM_SIGNATURE);
+        assert_clean_error("partial-jxlc", &partial_box);
+    }
+
+    #[test]
+    fn test_tick_rate_animations() {
+        // (file, ticks per second, frame durations in ms, loop count, timecodes)
+        let cases = [
+            ("ntsc.jxl", TickRate::new(30000, 1001), &[1001.0 / 30.0, 2002.0 / 30.0, 100.1][..], 3, false),
+            ("seven-tps.jxl", TickRate::new(7, 1), &[1000.0 / 7.0, 5000.0 / 7.0][..], 1, false),
+            ("timecodes.jxl", TickRate::new(1000, 1), &[500.0, 500.0][..], 0, true),
+        ];
+
+        for (file, rate, expected, loops, timecodes) in cases {
+            let path = Path::new(ANIMATION_CORPUS).join(file);
+            let Ok(DecodeResult::Animation { frames, metadata }) = decode_jxl(&path, &DecodeOptions::default()) else {
+                panic!("{}: not decoded as an animation", file);
+            };
+            assert_eq!(metadata.tick_rate, Some(rate), "{}", file);
+            assert_eq!(metadata.loop_count, loops, "{}", file);
+            assert_eq!(metadata.have_timecodes, timecodes, "{}", file);
+
+            let durations: Vec<f64> = frames.iter().map(|f| f.duration_ms).collect();
+            assert_eq!(durations.len(), expected.len(), "{}: frame count", file);
+            for (actual, expected) in durations.iter().zip(expected) {
+                assert!((actual - expected).abs() < 1e-9, "{}: {:?} != {:?}", file, durations, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_truncated_animations() {
+        let entries = std::fs::read_dir(ANIMATION_CORPUS).expect("animation corpus is missing");
+        let paths: Vec<PathBuf> = entries
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().and_then(|ext| ext.to_str()) == Some("jxl"))
+            .collect();
+        assert!(!paths.is_empty(), "no animations in {}", ANIMATION_CORPUS);
+
+        for path in paths {
+            let bytes = std::fs::read(&path).unwrap();
+            let name = path.file_stem().unwrap().to_string_lossy().to_string();
+
+            // The complete file decodes to an animation
+            let frame_count = match catch_panic(|| decode_jxl(&path, &DecodeOptions::default())) {
+                Ok(DecodeResult::Animation { frames, .. }) => frames.len(),
+                Ok(DecodeResult::SingleFrame { .. }) => panic!("{}: not an animation", name),
+                Err(e) => panic!("{}: {}", name, e),
+            };
+
+            // Cutting into the codestream is an error, never a panic or a
+            // shorter animation. Trailing metadata boxes may still decode.
+            let step = (bytes.len() / 64).max(1);
+            for len in (0..bytes.len()).step_by(step) {
+                let truncated = format!("{}-{}", name, len);
+                if let Ok(DecodeResult::Animation { frames, .. }) = decode_without_panic(&truncated, &bytes[..len]) {
+                    assert_eq!(frames.len(), frame_count, "{}: frames dropped", truncated);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,4 @@
+//! Decoding pipeline of JXL-UI, shared by the viewer, integration tests and tools
+
+pub mod decoder;
+pub mod conformance;
@@ -1,10 +1,25 @@
 //! JXL-UI - A beautiful cross-platform JPEG XL viewer
 
-mod decoder;
-
+mod animation;
+mod clipboard;
+mod compare;
+mod config;
+mod crop;
+mod orientation;
+mod slideshow;
+mod thumbnail_cache;
+mod thumbnails;
+mod tiles;
+mod watcher;
+
+use animation::{AnimationState, PlaybackMode, PlaybackView};
+use config::Settings;
+use orientation::Orientation;
 use eframe::egui::{self, Color32, RichText, Rounding, Stroke, Vec2};
+use jxl_ui::{conformance, decoder};
 use std::path::PathBuf;
 use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Arc;
 use std::thread;
 use std::time::{Duration, Instant};
 
@@ -36,12 +51,19 @@ mod theme {
     // Semantic
     pub const ERROR: Color32 = Color32::from_rgb(239, 68, 68);
     pub const SUCCESS: Color32 = Color32::from_rgb(34, 197, 94);
+    pub const WARNING: Color32 = Color32::from_rgb(245, 158, 11);
 }
 
 fn main() -> eframe::Result<()> {
     env_logger::init();
 
     let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) == Some("conformance") {
+        std::process::exit(run_conformance(args.get(2)));
+    }
+    if args.get(1).map(String::as_str) == Some("thumbnail") {
+        std::process::exit(run_thumbnailer(&args[2..]));
+    }
     let initial_file = args.get(1).map(PathBuf::from);
 
     let options = eframe::NativeOptions {
@@ -63,6 +85,59 @@ fn main() -> eframe::Result<()> {
     )
 }
 
+/// `jxl-ui conformance <dir>`: run the conformance suite and print a pass/fail matrix
+fn run_conformance(dir: Option<&String>) -> i32 {
+    let Some(dir) = dir else {
+        eprintln!("Usage: jxl-ui conformance <conformance checkout>");
+        return 2;
+    };
+    match conformance::run_suite(std::path::Path::new(dir)) {
+        Ok(results) => {
+            print!("{}", conformance::format_matrix(&results));
+            if results.iter().all(|r| r.outcome.is_pass()) { 0 } else { 1 }
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            2
+        }
+    }
+}
+
+/// Write a thumbnail for a file manager: `jxl-ui thumbnail [-s SIZE] INPUT OUTPUT`
+///
+/// INPUT may be a path or a `file://` URI, matching the `%i` and `%u` fields of
+/// a `.thumbnailer` entry.
+fn run_thumbnailer(args: &[String]) -> i32 {
+    let (size, paths) = match args {
+        [flag, size, rest @ ..] if flag == "-s" => (size.parse().ok(), rest),
+        rest => (Some(thumbnails::THUMBNAIL_SIZE), rest),
+    };
+    let (Some(size), [input, output]) = (size.filter(|&s| s > 0), paths) else {
+        eprintln!("Usage: jxl-ui thumbnail [-s SIZE] <input> <output.png>");
+        return 2;
+    };
+
+    let input = match input.strip_prefix("file://") {
+        Some(uri) => PathBuf::from(clipboard::percent_decode(uri.strip_prefix("localhost").unwrap_or(uri))),
+        None => PathBuf::from(input),
+    };
+    // File managers run this on every file in a folder, and the user's settings
+    // do not apply, so huge images are always refused at the default limit
+    let options = decoder::worker::DecodeOptions {
+        pixel_limit: Settings::default().pixel_limit(),
+        ..Default::default()
+    };
+    let result = thumbnail_cache::generate(&input, size, &options)
+        .and_then(|thumbnail| thumbnail_cache::save(std::path::Path::new(output), &thumbnail, &input));
+    match result {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("{}: {}", input.display(), e);
+            1
+        }
+    }
+}
+
 fn setup_fonts(ctx: &egui::Context) {
     // Use egui's excellent default fonts (Hack for mono, Ubuntu-Light for proportional)
     // They're already high quality and cross-platform
@@ -132,38 +207,149 @@ enum DecoderMessage {
         completed_passes: usize,
         is_final: bool,
         elapsed: Duration,
+        conversion: Duration,
     },
     AnimationFrame {
         rgba: Vec<u8>,
         width: u32,
         height: u32,
-        duration_ms: u32,
+        duration_ms: f64,
         frame_index: usize,
         total_frames: usize,
+        loop_count: u32,
+    },
+    /// Embedded preview of a large image, shown until the full decode finishes
+    Preview {
+        rgba: Vec<u8>,
+        width: u32,
+        height: u32,
+        full_size: (usize, usize),
     },
+    ImageInfo(decoder::ImageMetadata),
+    Metadata(decoder::metadata::ContainerMetadata),
+    Structure(Result<decoder::structure::FileStructure, String>),
+    /// A URL finished downloading to this file, which is decoded next
+    Downloaded(PathBuf),
     Complete,
     Error(String),
+    /// The decoder panicked; shown apart from ordinary decode errors
+    Crashed(String),
+}
+
+/// Decoded RGBA8 pixels shared between a tab and background jobs
+type SharedRgba = Arc<Vec<u8>>;
+
+/// Playback speeds offered in the status bar
+const PLAYBACK_SPEEDS: [f32; 5] = [0.25, 0.5, 1.0, 2.0, 4.0];
+
+enum CompareMessage {
+    Metrics(Result<compare::Metrics, String>),
+    Difference {
+        rgba: Vec<u8>,
+        width: u32,
+        height: u32,
+    },
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum CompareView {
+    Image,
+    Difference,
+    Heatmap,
+}
+
+/// Comparison of a tab against another open tab
+struct CompareState {
+    other_tab_id: usize,
+    view: CompareView,
+    amplification: f32,
+    metrics: Option<Result<compare::Metrics, String>>,
+    diff_texture: Option<egui::TextureHandle>,
+    // Pixels (this tab, other tab) the current results were computed from
+    sources: Option<(SharedRgba, SharedRgba)>,
+    metrics_dirty: bool,
+    diff_dirty: bool,
+    metrics_pending: bool,
+    diff_pending: bool,
+    tx: Sender<CompareMessage>,
+    rx: Receiver<CompareMessage>,
+}
+
+impl CompareState {
+    fn new(other_tab_id: usize) -> Self {
+        let (tx, rx) = channel();
+        Self {
+            other_tab_id,
+            view: CompareView::Image,
+            amplification: 8.0,
+            metrics: None,
+            diff_texture: None,
+            sources: None,
+            metrics_dirty: true,
+            diff_dirty: true,
+            metrics_pending: false,
+            diff_pending: false,
+            tx,
+            rx,
+        }
+    }
+}
+
+struct Layer {
+    name: String,
+    texture: egui::TextureHandle,
+    rgba: SharedRgba,
+    size: (u32, u32),
+    is_cropped: bool,
+    visible: bool,
 }
 
-struct AnimationState {
-    frames: Vec<egui::TextureHandle>,
-    durations: Vec<u32>,
-    current_frame: usize,
-    last_frame_time: Instant,
-    is_playing: bool,
+/// Layers of a multi-layer image, decoded with coalescing disabled
+struct LayerState {
+    layers: Vec<Layer>,
+    solo: Option<usize>,
+    composite: Option<egui::TextureHandle>,
+    composite_dirty: bool,
+    error: Option<String>,
+    rx: Option<Receiver<Result<Vec<decoder::DecodedLayer>, String>>>,
 }
 
 struct ImageTab {
     id: usize,
     title: String,
     file_path: Option<PathBuf>,
-    texture: Option<egui::TextureHandle>,
+    texture: Option<tiles::TiledImage>,
+    rgba: Option<SharedRgba>,
     animation: Option<AnimationState>,
     dimensions: Option<(u32, u32)>,
     decode_time: Option<Duration>,
+    conversion_time: Option<Duration>,
+    image_info: Option<decoder::ImageMetadata>,
+    metadata: Option<decoder::metadata::ContainerMetadata>,
+    structure: Option<Result<decoder::structure::FileStructure, String>>,
     is_loading: bool,
     error: Option<String>,
+    crashed: bool, // The error is a decoder panic rather than a bad file
     decoder_rx: Option<Receiver<DecoderMessage>>,
+    compare: Option<CompareState>,
+    layers: Option<LayerState>,
+    autoplay: bool,
+    /// Re-decode the file when it changes on disk
+    auto_reload: bool,
+    /// Playback state to apply once decoding completes, after a reload or restore
+    restore_view: Option<PlaybackView>,
+    /// Pixels were dropped to stay within the memory budget; decode again when shown
+    evicted: bool,
+    /// The image shown is from before a reload, replaced once new pixels arrive
+    stale: bool,
+    /// Rotation and flips applied on screen
+    view_orientation: Orientation,
+    /// Decode without applying the header orientation, showing pixels as stored
+    raw_orientation: bool,
+    /// Region dragged out on the image, in image pixels
+    selection: Option<crop::Selection>,
+    /// Where the current drag started, in 0..1 image coordinates
+    selection_start: Option<egui::Pos2>,
 }
 
 impl ImageTab {
@@ -173,88 +359,186 @@ impl ImageTab {
             title: "New Tab".to_string(),
             file_path: None,
             texture: None,
+            rgba: None,
             animation: None,
             dimensions: None,
             decode_time: None,
+            conversion_time: None,
+            image_info: None,
+            metadata: None,
+            structure: None,
             is_loading: false,
             error: None,
+            crashed: false,
             decoder_rx: None,
+            compare: None,
+            layers: None,
+            autoplay: true,
+            auto_reload: true,
+            restore_view: None,
+            evicted: false,
+            stale: false,
+            view_orientation: Orientation::IDENTITY,
+            raw_orientation: false,
+            selection: None,
+            selection_start: None,
         }
     }
 
-    fn load_file(&mut self, path: PathBuf) {
-        self.title = path.file_name()
+    fn load_file(&mut self, path: PathBuf, settings: &Settings) {
+        let title = path.file_name()
             .map(|n| n.to_string_lossy().to_string())
             .unwrap_or_else(|| "Image".to_string());
+        let (options, tx) = self.start_loading(title, settings);
+        self.file_path = Some(path.clone());
+
+        thread::spawn(move || {
+            decode_file(path, options, tx);
+        });
+    }
+
+    /// Download `url` to a temporary file, then decode it like a local file
+    fn load_url(&mut self, url: String, settings: &Settings) {
+        let (options, tx) = self.start_loading(clipboard::url_file_name(&url), settings);
+        self.file_path = None;
+
+        thread::spawn(move || {
+            match download(&url) {
+                Ok(path) => {
+                    let _ = tx.send(DecoderMessage::Downloaded(path.clone()));
+                    decode_file(path, options, tx);
+                }
+                Err(e) => {
+                    let _ = tx.send(DecoderMessage::Error(format!("Failed to download {}: {}", url, e)));
+                }
+            }
+        });
+    }
+
+    /// Clear the previous image and open a new decoder channel
+    fn start_loading(&mut self, title: String, settings: &Settings) -> (decoder::worker::DecodeOptions, Sender<DecoderMessage>) {
+        self.title = title;
         self.is_loading = true;
+        self.evicted = false;
+        self.stale = false;
         self.error = None;
+        self.crashed = false;
         self.texture = None;
+        self.rgba = None;
         self.animation = None;
         self.dimensions = None;
         self.decode_time = None;
-        self.file_path = Some(path.clone());
+        self.conversion_time = None;
+        self.image_info = None;
+        self.metadata = None;
+        self.structure = None;
+        self.layers = None;
+        self.autoplay = settings.autoplay;
+        let options = self.decode_options(settings);
 
         let (tx, rx) = channel();
         self.decoder_rx = Some(rx);
+        (options, tx)
+    }
 
-        thread::spawn(move || {
-            decode_file(path, tx);
-        });
+    /// Decoder settings for this tab's file
+    fn decode_options(&self, settings: &Settings) -> decoder::worker::DecodeOptions {
+        decoder::worker::DecodeOptions {
+            pixel_limit: settings.pixel_limit(),
+            memory_limit: Some(settings.memory_budget_mb * 1_048_576),
+            adjust_orientation: !self.raw_orientation,
+            threads: settings.decoder_threads(),
+        }
     }
 
     fn process_messages(&mut self, ctx: &egui::Context) {
         if let Some(rx) = &self.decoder_rx {
             while let Ok(msg) = rx.try_recv() {
                 match msg {
-                    DecoderMessage::ProgressiveUpdate { rgba, width, height, completed_passes, is_final, elapsed } => {
-                        let image = egui::ColorImage::from_rgba_unmultiplied(
-                            [width as usize, height as usize],
-                            &rgba,
-                        );
-                        self.texture = Some(ctx.load_texture(
+                    DecoderMessage::ProgressiveUpdate { rgba, width, height, completed_passes, is_final, elapsed, conversion } => {
+                        self.drop_stale();
+                        let rgba = Arc::new(rgba);
+                        self.texture = Some(tiles::TiledImage::new(
                             format!("tab-{}-pass-{}", self.id, completed_passes),
-                            image,
-                            egui::TextureOptions::LINEAR,
+                            rgba.clone(),
+                            width as usize,
+                            height as usize,
+                            ctx.input(|i| i.max_texture_side),
                         ));
+                        self.rgba = Some(rgba);
                         self.dimensions = Some((width, height));
                         if is_final {
                             self.decode_time = Some(elapsed);
+                            self.conversion_time = Some(conversion);
                             self.is_loading = false;
                         }
                     }
-                    DecoderMessage::AnimationFrame { rgba, width, height, duration_ms, frame_index, total_frames } => {
-                        let image = egui::ColorImage::from_rgba_unmultiplied(
-                            [width as usize, height as usize],
-                            &rgba,
-                        );
-                        let texture = ctx.load_texture(
+                    DecoderMessage::AnimationFrame { rgba, width, height, duration_ms, frame_index, total_frames, loop_count } => {
+                        self.drop_stale();
+                        let rgba = Arc::new(rgba);
+                        let texture = tiles::TiledImage::new(
                             format!("tab-{}-frame-{}", self.id, frame_index),
-                            image,
-                            egui::TextureOptions::LINEAR,
+                            rgba.clone(),
+                            width as usize,
+                            height as usize,
+                            ctx.input(|i| i.max_texture_side),
                         );
 
                         if self.animation.is_none() {
-                            self.animation = Some(AnimationState {
-                                frames: Vec::with_capacity(total_frames),
-                                durations: Vec::with_capacity(total_frames),
-                                current_frame: 0,
-                                last_frame_time: Instant::now(),
-                                is_playing: true,
-                            });
+                            self.texture = None; // Drop any preview
+                            self.animation = Some(AnimationState::new(total_frames, loop_count, self.autoplay));
                         }
 
                         if let Some(anim) = &mut self.animation {
-                            anim.frames.push(texture);
-                            anim.durations.push(duration_ms);
+                            anim.push(texture, rgba, duration_ms);
                         }
 
                         self.dimensions = Some((width, height));
                     }
+                    DecoderMessage::Preview { rgba, width, height, full_size } => {
+                        // Only stands in until the first real pass arrives; the
+                        // preview decodes alongside the full image and may lose
+                        if self.is_loading && self.texture.is_none() && self.animation.is_none() {
+                            self.texture = Some(tiles::TiledImage::new(
+                                format!("tab-{}-preview", self.id),
+                                Arc::new(rgba),
+                                width as usize,
+                                height as usize,
+                                ctx.input(|i| i.max_texture_side),
+                            ).with_display_size(full_size.0, full_size.1));
+                            self.dimensions = Some((full_size.0 as u32, full_size.1 as u32));
+                        }
+                    }
+                    DecoderMessage::ImageInfo(info) => {
+                        self.image_info = Some(info);
+                    }
+                    DecoderMessage::Metadata(metadata) => {
+                        self.metadata = Some(metadata);
+                    }
+                    DecoderMessage::Structure(structure) => {
+                        self.structure = Some(structure);
+                    }
+                    DecoderMessage::Downloaded(path) => {
+                        self.file_path = Some(path);
+                    }
                     DecoderMessage::Complete => {
                         self.is_loading = false;
+                        if let (Some(anim), Some(view)) = (&mut self.animation, self.restore_view.take()) {
+                            anim.seek(view.frame);
+                            anim.is_playing = view.is_playing;
+                            anim.speed = view.speed;
+                            anim.mode = view.mode;
+                        }
                     }
                     DecoderMessage::Error(e) => {
+                        self.drop_stale();
+                        self.error = Some(e);
+                        self.is_loading = false;
+                    }
+                    DecoderMessage::Crashed(e) => {
+                        self.drop_stale();
                         self.error = Some(e);
+                        self.crashed = true;
                         self.is_loading = false;
                     }
                 }
@@ -262,42 +546,298 @@ impl ImageTab {
         }
     }
 
+    /// Decode the file again after it changed on disk, keeping the playback state
+    ///
+    /// The old image stays on screen until the first pixels of the new one arrive.
+    fn reload(&mut self, settings: &Settings) {
+        let Some(path) = self.file_path.clone() else {
+            return;
+        };
+        let view = self.playback_view();
+        let shown = (self.texture.take(), self.rgba.take(), self.animation.take(), self.dimensions);
+        log::info!("Reloading {:?}", path);
+        self.load_file(path, settings);
+        (self.texture, self.rgba, self.animation, self.dimensions) = shown;
+        self.stale = self.texture.is_some() || self.animation.is_some();
+        self.restore_view = view;
+    }
+
+    /// Clear the image kept from before a reload
+    fn drop_stale(&mut self) {
+        if std::mem::take(&mut self.stale) {
+            self.texture = None;
+            self.rgba = None;
+            self.animation = None;
+        }
+    }
+
+    fn playback_view(&self) -> Option<PlaybackView> {
+        self.animation.as_ref().map(|anim| PlaybackView {
+            frame: anim.current_frame,
+            is_playing: anim.is_playing,
+            speed: anim.speed,
+            mode: anim.mode,
+        })
+    }
+
+    /// Drop the decoded pixels of a tab that is not shown; `restore` decodes them again
+    fn evict(&mut self) {
+        if self.evicted || self.is_loading || self.file_path.is_none() {
+            return;
+        }
+        log::debug!("Evicting {} to stay within the memory budget", self.title);
+        self.restore_view = self.playback_view();
+        self.texture = None;
+        self.rgba = None;
+        self.animation = None;
+        self.layers = None;
+        self.evicted = true;
+    }
+
+    /// Decode an evicted tab again, keeping its title and playback state
+    fn restore(&mut self, settings: &Settings) {
+        let Some(path) = self.file_path.clone().filter(|_| self.evicted) else {
+            return;
+        };
+        let title = std::mem::take(&mut self.title);
+        self.load_file(path, settings);
+        self.title = title;
+    }
+
+    /// Path and view state, for restoring the tab later
+    fn session_tab(&self, path: PathBuf) -> config::SessionTab {
+        config::SessionTab {
+            path,
+            frame: self.animation.as_ref()
+                .filter(|a| !a.is_playing)
+                .map(|a| a.current_frame),
+            orientation: (!self.view_orientation.is_identity()).then(|| self.view_orientation.to_exif()),
+            raw_orientation: self.raw_orientation,
+        }
+    }
+
+    /// Texture memory held by the image and animation frames
+    fn resident_bytes(&self) -> usize {
+        let frames = self.animation.iter().flat_map(|a| &a.frames);
+        self.texture.iter().chain(frames).map(tiles::TiledImage::resident_bytes).sum()
+    }
+
+    /// Decoded pixels held on the CPU, including downscaled levels and layers
+    ///
+    /// `rgba` and `animation.rgba` share their buffers with the first level
+    /// of the tiled images, so they are not counted again.
+    fn cpu_bytes(&self) -> usize {
+        let frames = self.animation.iter().flat_map(|a| &a.frames);
+        let images: usize = self.texture.iter().chain(frames).map(tiles::TiledImage::cpu_bytes).sum();
+        let layers: usize = self.layers.iter()
+            .flat_map(|l| &l.layers)
+            .map(|l| l.rgba.len() * 2) // Pixels plus their texture
+            .sum();
+        images + layers
+    }
+
+    /// Release all image and animation textures; they are uploaded again when shown
+    fn release_textures(&mut self) {
+        let frames = self.animation.iter_mut().flat_map(|a| &mut a.frames);
+        self.texture.iter_mut().chain(frames).for_each(tiles::TiledImage::release);
+    }
+
+    /// Pixels of the currently displayed frame, with its dimensions
+    fn current_rgba(&self) -> Option<(SharedRgba, (u32, u32))> {
+        let dims = self.dimensions?;
+        if let Some(anim) = &self.animation {
+            anim.rgba.get(anim.current_frame).map(|rgba| (rgba.clone(), dims))
+        } else {
+            self.rgba.clone().map(|rgba| (rgba, dims))
+        }
+    }
+
+    /// Enter layers mode, decoding every frame separately in the background
+    fn start_layers(&mut self, settings: &Settings) {
+        let Some(path) = self.file_path.clone() else {
+            return;
+        };
+        let options = self.decode_options(settings);
+
+        let (tx, rx) = channel();
+        self.layers = Some(LayerState {
+            layers: Vec::new(),
+            solo: None,
+            composite: None,
+            composite_dirty: false,
+            error: None,
+            rx: Some(rx),
+        });
+
+        thread::spawn(move || {
+            let result = decoder::worker::catch_panic(|| decoder::worker::decode_layers(&path, &options))
+                .map_err(|e| e.to_string());
+            let _ = tx.send(result);
+        });
+    }
+
+    fn update_layers(&mut self, ctx: &egui::Context) {
+        let canvas = self.dimensions;
+        let Some(state) = &mut self.layers else {
+            return;
+        };
+
+        if let Some(rx) = &state.rx {
+            if let Ok(result) = rx.try_recv() {
+                state.rx = None;
+                match result {
+                    Ok(layers) => {
+                        for (i, layer) in layers.into_iter().enumerate() {
+                            let frame = layer.frame;
+                            let image = egui::ColorImage::from_rgba_unmultiplied(
+                                [frame.width as usize, frame.height as usize],
+                                &frame.rgba_data,
+                            );
+                            let texture = ctx.load_texture(
+                                format!("tab-{}-layer-{}", self.id, i),
+                                image,
+                                egui::TextureOptions::LINEAR,
+                            );
+                            state.layers.push(Layer {
+                                name: layer.name,
+                                texture,
+                                rgba: Arc::new(frame.rgba_data),
+                                size: (frame.width, frame.height),
+                                is_cropped: layer.is_cropped,
+                                visible: true,
+                            });
+                        }
+                        state.composite_dirty = true;
+                    }
+                    Err(e) => state.error = Some(e),
+                }
+            } else {
+                ctx.request_repaint();
+            }
+        }
+
+        if state.composite_dirty {
+            state.composite_dirty = false;
+            if let Some((width, height)) = canvas {
+                // Cropped layers cannot be placed on the canvas without their offset
+                let visible: Vec<&Layer> = state.layers.iter().filter(|l| l.visible && !l.is_cropped).collect();
+                let rgba = composite_layers(&visible, width, height);
+                let image = egui::ColorImage::from_rgba_unmultiplied(
+                    [width as usize, height as usize],
+                    &rgba,
+                );
+                state.composite = Some(ctx.load_texture(
+                    format!("tab-{}-composite", self.id),
+                    image,
+                    egui::TextureOptions::LINEAR,
+                ));
+            }
+        }
+    }
+
     fn update_animation(&mut self, ctx: &egui::Context) {
         if let Some(anim) = &mut self.animation {
-            if anim.is_playing && !anim.frames.is_empty() {
-                let current_duration = anim.durations.get(anim.current_frame).copied().unwrap_or(100);
-                if anim.last_frame_time.elapsed() >= Duration::from_millis(current_duration as u64) {
-                    anim.current_frame = (anim.current_frame + 1) % anim.frames.len();
-                    anim.last_frame_time = Instant::now();
-                    ctx.request_repaint();
-                }
-                ctx.request_repaint_after(Duration::from_millis(current_duration as u64));
+            let now = Instant::now();
+            let dt = now - anim.last_tick;
+            anim.last_tick = now;
+
+            let frame = anim.current_frame;
+            if let Some(next_change) = anim.tick(dt) {
+                ctx.request_repaint_after(next_change);
+            }
+            if anim.current_frame != frame {
+                ctx.request_repaint();
             }
         }
     }
 }
 
+#[derive(Clone, Copy, PartialEq)]
+enum ThumbnailView {
+    /// A row of thumbnails below the image
+    Strip,
+    /// A grid of thumbnails in place of the image
+    Sheet,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum ThumbnailSource {
+    Tabs,
+    /// JXL files next to the active tab's file
+    Folder,
+}
+
+/// An image shown in the filmstrip or contact sheet
+struct ThumbnailItem {
+    path: PathBuf,
+    label: String,
+    /// Open tab showing this file
+    tab: Option<usize>,
+}
+
 struct JxlApp {
     tabs: Vec<ImageTab>,
     active_tab: usize,
     next_tab_id: usize,
     show_about: bool,
+    show_settings: bool,
+    /// Settings as they were when the settings window opened, to save changes on close
+    settings_before: Option<Settings>,
     show_info: bool,
+    show_structure: bool,
+    settings: Settings,
+    recent_files: Vec<PathBuf>,
+    /// Closed tabs as (position, path and view), most recent last
+    closed_tabs: Vec<(usize, config::SessionTab)>,
+    watcher: Option<watcher::FileWatcher>,
+    /// Opened on first use
+    clipboard: Option<clipboard::SystemClipboard>,
+    /// A paste event arrived since the last Cmd+V release
+    paste_handled: bool,
+    /// Tab bar, status bar and timeline hidden
+    fullscreen: bool,
+    slideshow: Option<slideshow::Slideshow>,
+    thumbnails: thumbnails::Thumbnails,
+    thumbnail_view: Option<ThumbnailView>,
+    thumbnail_source: ThumbnailSource,
 }
 
 impl JxlApp {
-    fn new(_cc: &eframe::CreationContext<'_>, initial_file: Option<PathBuf>) -> Self {
+    fn new(cc: &eframe::CreationContext<'_>, initial_file: Option<PathBuf>) -> Self {
+        let config = config::Config::load();
+        thread::spawn(clipboard::remove_stale_temp_files);
+        let watcher = watcher::FileWatcher::new(cc.egui_ctx.clone())
+            .map_err(|e| log::warn!("File watching unavailable: {}", e))
+            .ok();
         let mut app = Self {
             tabs: vec![],
             active_tab: 0,
             next_tab_id: 0,
             show_about: false,
-            show_info: false,
+            show_settings: false,
+            settings_before: None,
+            show_info: config.settings.show_info,
+            show_structure: config.settings.show_structure,
+            settings: config.settings,
+            recent_files: config.recent_files,
+            closed_tabs: Vec::new(),
+            watcher,
+            clipboard: None,
+            paste_handled: false,
+            fullscreen: false,
+            slideshow: None,
+            thumbnails: thumbnails::Thumbnails::new(cc.egui_ctx.clone()),
+            thumbnail_view: None,
+            thumbnail_source: ThumbnailSource::Tabs,
         };
 
         if let Some(path) = initial_file {
             app.open_file_in_new_tab(path);
         } else {
+            app.restore_session(&config.session);
+        }
+
+        if app.tabs.is_empty() {
             app.tabs.push(ImageTab::new(app.next_tab_id));
             app.next_tab_id += 1;
         }
@@ -305,10 +845,72 @@ impl JxlApp {
         app
     }
 
+    /// Reopen the tabs of a previous session, skipping files that no longer exist
+    fn restore_session(&mut self, session: &config::Session) {
+        let mut active_tab = 0;
+        for (i, saved) in session.tabs.iter().enumerate() {
+            if !saved.path.exists() {
+                log::warn!("Not restoring missing file {:?}", saved.path);
+                continue;
+            }
+            if i <= session.active_tab {
+                active_tab = self.tabs.len();
+            }
+            // Restoring is not opening, so the recent files keep their order
+            self.restore_tab(saved);
+        }
+        self.active_tab = active_tab;
+    }
+
+    /// Current settings and open tabs, for saving on exit
+    fn config(&self) -> config::Config {
+        let mut settings = self.settings.clone();
+        settings.show_info = self.show_info;
+        settings.show_structure = self.show_structure;
+
+        let mut session = config::Session::default();
+        for (i, tab) in self.tabs.iter().enumerate() {
+            let Some(path) = &tab.file_path else {
+                continue;
+            };
+            if i == self.active_tab {
+                session.active_tab = session.tabs.len();
+            }
+            session.tabs.push(tab.session_tab(path.clone()));
+        }
+
+        config::Config {
+            settings,
+            session,
+            recent_files: self.recent_files.clone(),
+        }
+    }
+
     fn open_file_in_new_tab(&mut self, path: PathBuf) {
+        config::push_recent(&mut self.recent_files, path.clone());
+        self.open_tab(path);
+    }
+
+    /// Open `path` in a new tab without adding it to the recent files
+    fn open_tab(&mut self, path: PathBuf) {
+        let mut tab = ImageTab::new(self.next_tab_id);
+        self.next_tab_id += 1;
+        tab.load_file(path, &self.settings);
+        self.tabs.push(tab);
+        self.active_tab = self.tabs.len() - 1;
+    }
+
+    /// Open a saved tab with its view state, without adding it to the recent files
+    fn restore_tab(&mut self, saved: &config::SessionTab) {
         let mut tab = ImageTab::new(self.next_tab_id);
         self.next_tab_id += 1;
-        tab.load_file(path);
+        tab.restore_view = saved.frame.map(PlaybackView::paused_at);
+        tab.view_orientation = saved.orientation
+            .and_then(Orientation::from_exif)
+            .unwrap_or(Orientation::IDENTITY);
+        // Set before loading, since it decides how the file is decoded
+        tab.raw_orientation = saved.raw_orientation;
+        tab.load_file(saved.path.clone(), &self.settings);
         self.tabs.push(tab);
         self.active_tab = self.tabs.len() - 1;
     }
@@ -323,82 +925,494 @@ impl JxlApp {
         }
     }
 
-    fn close_tab(&mut self, index: usize) {
-        if self.tabs.len() > 1 {
-            self.tabs.remove(index);
-            if self.active_tab >= self.tabs.len() {
-                self.active_tab = self.tabs.len() - 1;
+    /// Pixels of the active tab's selection, with straight alpha
+    fn selected_pixels(&self) -> Option<(crop::Selection, Vec<u8>)> {
+        let tab = self.tabs.get(self.active_tab)?;
+        let (rgba, dims) = tab.current_rgba()?;
+        let selection = tab.selection.filter(|s| s.fits(dims))?;
+        let mut pixels = selection.crop(&rgba, dims.0);
+        crop::unpremultiply(&mut pixels);
+        Some((selection, pixels))
+    }
+
+    fn export_selection(&mut self) {
+        let Some((selection, pixels)) = self.selected_pixels() else {
+            return;
+        };
+        let stem = self.tabs[self.active_tab].file_path.as_ref()
+            .and_then(|p| p.file_stem())
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_else(|| "image".to_string());
+        let file_name = format!(
+            "{}-{}x{}+{}+{}.png",
+            stem, selection.width, selection.height, selection.x, selection.y
+        );
+
+        if let Some(path) = rfd::FileDialog::new()
+            .add_filter("PNG", &["png"])
+            .set_file_name(file_name)
+            .save_file()
+        {
+            if let Err(e) = crop::save_png(&path, &pixels, selection.width, selection.height) {
+                log::warn!("Failed to export {:?}: {}", path, e);
             }
         }
     }
-}
 
-impl eframe::App for JxlApp {
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        // Process messages
-        for tab in &mut self.tabs {
-            tab.process_messages(ctx);
-            tab.update_animation(ctx);
+    fn clipboard(&mut self) -> Option<&mut dyn clipboard::Clipboard> {
+        if self.clipboard.is_none() {
+            self.clipboard = clipboard::SystemClipboard::new()
+                .map_err(|e| log::warn!("Clipboard unavailable: {}", e))
+                .ok();
         }
+        self.clipboard.as_mut().map(|c| c as &mut dyn clipboard::Clipboard)
+    }
 
-        // Tab bar at top
-        egui::TopBottomPanel::top("tab_bar")
-            .frame(egui::Frame::none()
-                .fill(theme::BG_ELEVATED)
-                .inner_margin(egui::Margin { left: 12.0, right: 12.0, top: 8.0, bottom: 0.0 }))
-            .show_separator_line(false)
-            .show(ctx, |ui| {
-                ui.horizontal(|ui| {
-                    ui.style_mut().spacing.item_spacing.x = 2.0;
-
-                    let mut tab_to_close: Option<usize> = None;
+    /// Copy the selection, or the whole displayed frame if nothing is selected
+    fn copy_image(&mut self) {
+        let (width, height, pixels) = match self.selected_pixels() {
+            Some((selection, pixels)) => (selection.width, selection.height, pixels),
+            None => {
+                let Some((rgba, (width, height))) = self.tabs.get(self.active_tab).and_then(|t| t.current_rgba()) else {
+                    return;
+                };
+                let mut pixels = rgba.to_vec();
+                crop::unpremultiply(&mut pixels);
+                (width, height, pixels)
+            }
+        };
+        if let Some(clipboard) = self.clipboard() {
+            if let Err(e) = clipboard.set_image(width, height, pixels) {
+                log::warn!("Failed to copy image: {}", e);
+            }
+        }
+    }
 
-                    for (i, tab) in self.tabs.iter().enumerate() {
-                        let is_active = i == self.active_tab;
+    fn copy_path(&mut self) {
+        let Some(path) = self.tabs.get(self.active_tab).and_then(|t| t.file_path.clone()) else {
+            return;
+        };
+        if let Some(clipboard) = self.clipboard() {
+            if let Err(e) = clipboard.set_text(path.display().to_string()) {
+                log::warn!("Failed to copy path: {}", e);
+            }
+        }
+    }
 
-                        let (bg_color, text_color, border_bottom) = if is_active {
-                            (theme::BG_BASE, theme::TEXT_PRIMARY, theme::ACCENT)
-                        } else {
-                            (Color32::TRANSPARENT, theme::TEXT_MUTED, Color32::TRANSPARENT)
-                        };
+    /// Open whatever the clipboard holds in a new tab: JXL data, a file or a URL
+    fn paste(&mut self) {
+        let Some(target) = self.clipboard().and_then(clipboard::paste_target) else {
+            return;
+        };
+        match target {
+            clipboard::PasteTarget::File(path) => self.open_file_in_new_tab(path),
+            clipboard::PasteTarget::Url(url) => {
+                let mut tab = ImageTab::new(self.next_tab_id);
+                self.next_tab_id += 1;
+                tab.load_url(url, &self.settings);
+                self.tabs.push(tab);
+                self.active_tab = self.tabs.len() - 1;
+            }
+            clipboard::PasteTarget::Bytes(bytes) => match clipboard::save_temp("pasted.jxl", &bytes) {
+                Ok(path) => self.open_file_in_new_tab(path),
+                Err(e) => log::warn!("Failed to save pasted image: {}", e),
+            },
+        }
+    }
 
-                        let response = ui.allocate_ui(Vec2::new(160.0, 36.0), |ui| {
-                            let rect = ui.available_rect_before_wrap();
+    fn thumbnail_items(&mut self) -> Vec<ThumbnailItem> {
+        match self.thumbnail_source {
+            ThumbnailSource::Tabs => self.tabs.iter().enumerate()
+                .filter_map(|(i, tab)| {
+                    tab.file_path.as_ref().map(|path| ThumbnailItem {
+                        path: path.clone(),
+                        label: tab.title.clone(),
+                        tab: Some(i),
+                    })
+                })
+                .collect(),
+            ThumbnailSource::Folder => {
+                let Some(current) = self.tabs.get(self.active_tab).and_then(|t| t.file_path.clone()) else {
+                    return Vec::new();
+                };
+                self.thumbnails.folder_files(&current).iter()
+                    .map(|path| ThumbnailItem {
+                        path: path.clone(),
+                        label: path.file_name()
+                            .map(|n| n.to_string_lossy().to_string())
+                            .unwrap_or_default(),
+                        tab: self.tabs.iter().position(|t| t.file_path.as_ref() == Some(path)),
+                    })
+                    .collect()
+            }
+        }
+    }
 
-                            // Background
-                            ui.painter().rect_filled(
-                                rect,
-                                Rounding { nw: 8.0, ne: 8.0, sw: 0.0, se: 0.0 },
-                                bg_color,
-                            );
+    /// Switch to the item's tab, or open it in a new one
+    fn open_thumbnail(&mut self, item: &ThumbnailItem) {
+        match item.tab {
+            Some(i) => self.active_tab = i,
+            None => self.open_file_in_new_tab(item.path.clone()),
+        }
+    }
 
-                            // Bottom accent line for active tab
-                            if is_active {
-                                ui.painter().rect_filled(
-                                    egui::Rect::from_min_size(
-                                        egui::pos2(rect.left(), rect.bottom() - 2.0),
-                                        Vec2::new(rect.width(), 2.0),
-                                    ),
-                                    Rounding::ZERO,
-                                    border_bottom,
-                                );
-                            }
+    fn set_fullscreen(&mut self, ctx: &egui::Context, fullscreen: bool) {
+        self.fullscreen = fullscreen;
+        ctx.send_viewport_cmd(egui::ViewportCommand::Fullscreen(fullscreen));
+    }
 
-                            ui.allocate_ui_at_rect(rect.shrink(8.0), |ui| {
-                                ui.horizontal_centered(|ui| {
-                                    // Loading indicator or icon
-                                    if tab.is_loading {
-                                        ui.spinner();
-                                    }
+    /// Cycle through the open tabs, or with only one tab, through its folder
+    fn toggle_slideshow(&mut self) {
+        if self.slideshow.take().is_some() {
+            return;
+        }
+        let files = match self.tabs.get(self.active_tab).and_then(|t| t.file_path.as_ref()) {
+            Some(path) if self.tabs.len() == 1 => slideshow::folder_files(path),
+            _ => Vec::new(),
+        };
+        if self.tabs.len() > 1 || files.len() > 1 {
+            self.slideshow = Some(slideshow::Slideshow::new(self.settings.slideshow_interval(), files));
+        }
+    }
 
-                                    // Title
-                                    let title = if tab.title.len() > 16 {
-                                        format!("{}…", &tab.title[..15])
-                                    } else {
-                                        tab.title.clone()
-                                    };
+    /// Move to the next image once the current one has been shown long enough
+    fn update_slideshow(&mut self, ctx: &egui::Context) {
+        let (Some(slideshow), Some(tab)) = (&mut self.slideshow, self.tabs.get_mut(self.active_tab)) else {
+            return;
+        };
+        let now = Instant::now();
+        // The interval starts once the image is on screen
+        if tab.is_loading {
+            slideshow.shown(now);
+            return;
+        }
+        let animation = tab.animation.as_ref().map(|a| (a.loops_completed, a.is_playing));
+        if !slideshow.is_due(now, animation) {
+            ctx.request_repaint_after(slideshow.remaining(now));
+            return;
+        }
 
-                                    ui.label(RichText::new(title)
+        slideshow.shown(now);
+        if slideshow.is_folder() {
+            if let Some(path) = tab.file_path.as_ref().and_then(|p| slideshow.next_file(p)) {
+                tab.load_file(path, &self.settings);
+            }
+        } else {
+            self.active_tab = (self.active_tab + 1) % self.tabs.len();
+        }
+        ctx.request_repaint();
+    }
+
+    /// Collect comparison results and start new jobs for the active tab
+    fn update_comparison(&mut self, ctx: &egui::Context) {
+        let Some(tab) = self.tabs.get(self.active_tab) else {
+            return;
+        };
+        let Some(other_id) = tab.compare.as_ref().map(|c| c.other_tab_id) else {
+            return;
+        };
+
+        let other = self.tabs.iter()
+            .find(|t| t.id == other_id)
+            .map(|t| t.current_rgba());
+        let current = tab.current_rgba();
+
+        let tab = &mut self.tabs[self.active_tab];
+        let Some(other) = other else {
+            // The other tab was closed
+            tab.compare = None;
+            return;
+        };
+        let Some(cmp) = &mut tab.compare else {
+            return;
+        };
+
+        while let Ok(msg) = cmp.rx.try_recv() {
+            match msg {
+                CompareMessage::Metrics(result) => {
+                    cmp.metrics = Some(result);
+                    cmp.metrics_pending = false;
+                }
+                CompareMessage::Difference { rgba, width, height } => {
+                    let image = egui::ColorImage::from_rgba_unmultiplied(
+                        [width as usize, height as usize],
+                        &rgba,
+                    );
+                    cmp.diff_texture = Some(ctx.load_texture(
+                        format!("tab-{}-diff", tab.id),
+                        image,
+                        egui::TextureOptions::NEAREST,
+                    ));
+                    cmp.diff_pending = false;
+                }
+            }
+        }
+
+        let (Some((a, a_dims)), Some((b, b_dims))) = (current, other) else {
+            return;
+        };
+
+        let unchanged = cmp.sources.as_ref()
+            .is_some_and(|(sa, sb)| Arc::ptr_eq(sa, &a) && Arc::ptr_eq(sb, &b));
+        if !unchanged {
+            cmp.sources = Some((a.clone(), b.clone()));
+            cmp.metrics_dirty = true;
+            cmp.diff_dirty = true;
+        }
+
+        if let Err(e) = compare::check_dimensions(a_dims, b_dims) {
+            cmp.metrics = Some(Err(e.to_string()));
+            cmp.diff_texture = None;
+            cmp.metrics_dirty = false;
+            cmp.diff_dirty = false;
+            return;
+        }
+
+        let (width, height) = a_dims;
+
+        if cmp.metrics_dirty && !cmp.metrics_pending {
+            cmp.metrics_dirty = false;
+            cmp.metrics_pending = true;
+            let (a, b, tx) = (a.clone(), b.clone(), cmp.tx.clone());
+            thread::spawn(move || {
+                let result = compare::compute_metrics(&a, &b, width as usize, height as usize)
+                    .map_err(|e| e.to_string());
+                let _ = tx.send(CompareMessage::Metrics(result));
+            });
+        }
+
+        let diff_mode = match cmp.view {
+            CompareView::Image => None,
+            CompareView::Difference => Some(compare::DiffMode::Absolute),
+            CompareView::Heatmap => Some(compare::DiffMode::Heatmap),
+        };
+        if let Some(mode) = diff_mode {
+            if cmp.diff_dirty && !cmp.diff_pending {
+                cmp.diff_dirty = false;
+                cmp.diff_pending = true;
+                let (amplification, tx) = (cmp.amplification, cmp.tx.clone());
+                thread::spawn(move || {
+                    let rgba = compare::difference_image(&a, &b, mode, amplification);
+                    let _ = tx.send(CompareMessage::Difference { rgba, width, height });
+                });
+            }
+        }
+
+        if cmp.metrics_pending || cmp.diff_pending {
+            ctx.request_repaint();
+        }
+    }
+
+    /// Reload tabs whose files changed on disk
+    fn update_watcher(&mut self, ctx: &egui::Context) {
+        let Some(watcher) = &mut self.watcher else {
+            return;
+        };
+
+        watcher.sync(self.tabs.iter()
+            .filter(|t| t.auto_reload)
+            .filter_map(|t| t.file_path.as_deref()));
+
+        for path in watcher.poll() {
+            self.thumbnails.invalidate(&path);
+            for tab in self.tabs.iter_mut().filter(|t| t.auto_reload) {
+                if tab.file_path.as_ref() == Some(&path) && path.exists() {
+                    tab.reload(&self.settings);
+                }
+            }
+        }
+
+        if watcher.has_pending() {
+            ctx.request_repaint_after(watcher::DEBOUNCE);
+        }
+    }
+
+    /// Free memory until textures and decoded pixels fit in the memory budget
+    ///
+    /// Inactive tabs release their textures first, then drop their decoded
+    /// pixels and are decoded again when activated. After that the animation
+    /// frames of the active tab furthest from being shown release their
+    /// textures, which are uploaded again when next drawn. Their pixels stay:
+    /// animations whose frames alone exceed the budget are refused while decoding.
+    fn enforce_memory_budget(&mut self) {
+        // The tab compared against is needed for the active tab's results
+        let partner = self.tabs.get(self.active_tab)
+            .and_then(|t| t.compare.as_ref())
+            .map(|c| c.other_tab_id);
+        for (i, tab) in self.tabs.iter_mut().enumerate() {
+            if i == self.active_tab || Some(tab.id) == partner {
+                tab.restore(&self.settings);
+            }
+        }
+
+        let budget = self.settings.memory_budget_mb * 1_048_576;
+        let mut total: usize = self.tabs.iter()
+            .map(|t| t.resident_bytes() + t.cpu_bytes())
+            .sum();
+        for (i, tab) in self.tabs.iter_mut().enumerate() {
+            if total <= budget {
+                return;
+            }
+            if i != self.active_tab {
+                total -= tab.resident_bytes();
+                tab.release_textures();
+            }
+        }
+        for (i, tab) in self.tabs.iter_mut().enumerate() {
+            if total <= budget {
+                return;
+            }
+            if i != self.active_tab && Some(tab.id) != partner && !tab.evicted {
+                let freed = tab.cpu_bytes();
+                tab.evict();
+                if tab.evicted {
+                    total -= freed;
+                }
+            }
+        }
+
+        let Some(anim) = self.tabs.get_mut(self.active_tab).and_then(|t| t.animation.as_mut()) else {
+            return;
+        };
+        let mut frames: Vec<usize> = (0..anim.frames.len())
+            .filter(|&i| i != anim.current_frame)
+            .collect();
+        frames.sort_by_key(|&i| std::cmp::Reverse(anim.frames_until(i)));
+        for i in frames {
+            if total <= budget {
+                return;
+            }
+            total -= anim.frames[i].resident_bytes();
+            anim.frames[i].release();
+        }
+    }
+
+    /// Save the config once the settings window closes, if anything changed
+    ///
+    /// Settings are otherwise only saved on exit, which a crash skips.
+    fn save_changed_settings(&mut self) {
+        if self.show_settings {
+            self.settings_before.get_or_insert_with(|| self.settings.clone());
+            return;
+        }
+        if self.settings_before.take().is_some_and(|before| before != self.settings) {
+            if let Err(e) = self.config().save() {
+                log::warn!("Failed to save settings: {}", e);
+            }
+        }
+    }
+
+    /// Reopen the most recently closed tab at its old position
+    fn reopen_closed_tab(&mut self) {
+        let Some((index, saved)) = self.closed_tabs.pop() else {
+            return;
+        };
+        config::push_recent(&mut self.recent_files, saved.path.clone());
+        self.restore_tab(&saved);
+        let tab = self.tabs.pop().unwrap();
+
+        let index = index.min(self.tabs.len());
+        self.tabs.insert(index, tab);
+        self.active_tab = index;
+    }
+
+    fn close_tab(&mut self, index: usize) {
+        if self.tabs.len() > 1 {
+            let tab = self.tabs.remove(index);
+            if let Some(path) = &tab.file_path {
+                self.closed_tabs.push((index, tab.session_tab(path.clone())));
+            }
+            if self.active_tab >= self.tabs.len() {
+                self.active_tab = self.tabs.len() - 1;
+            }
+        }
+    }
+}
+
+impl eframe::App for JxlApp {
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        if let Err(e) = self.config().save() {
+            log::warn!("Failed to save settings: {}", e);
+        }
+        clipboard::remove_temp_files();
+    }
+
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        // Process messages
+        for tab in &mut self.tabs {
+            tab.process_messages(ctx);
+            tab.update_animation(ctx);
+            tab.update_layers(ctx);
+        }
+        self.update_comparison(ctx);
+        self.update_watcher(ctx);
+        self.thumbnails.set_options(decoder::worker::DecodeOptions {
+            pixel_limit: self.settings.pixel_limit(),
+            threads: self.settings.decoder_threads(),
+            ..Default::default()
+        });
+        self.thumbnails.update(ctx);
+        self.update_slideshow(ctx);
+        self.enforce_memory_budget();
+
+        // Tab bar at top
+        egui::TopBottomPanel::top("tab_bar")
+            .frame(egui::Frame::none()
+                .fill(theme::BG_ELEVATED)
+                .inner_margin(egui::Margin { left: 12.0, right: 12.0, top: 8.0, bottom: 0.0 }))
+            .show_separator_line(false)
+            .show_animated(ctx, !self.fullscreen, |ui| {
+                ui.horizontal(|ui| {
+                    ui.style_mut().spacing.item_spacing.x = 2.0;
+
+                    let mut tab_to_close: Option<usize> = None;
+
+                    for (i, tab) in self.tabs.iter().enumerate() {
+                        let is_active = i == self.active_tab;
+
+                        let (bg_color, text_color, border_bottom) = if is_active {
+                            (theme::BG_BASE, theme::TEXT_PRIMARY, theme::ACCENT)
+                        } else {
+                            (Color32::TRANSPARENT, theme::TEXT_MUTED, Color32::TRANSPARENT)
+                        };
+
+                        let response = ui.allocate_ui(Vec2::new(160.0, 36.0), |ui| {
+                            let rect = ui.available_rect_before_wrap();
+
+                            // Background
+                            ui.painter().rect_filled(
+                                rect,
+                                Rounding { nw: 8.0, ne: 8.0, sw: 0.0, se: 0.0 },
+                                bg_color,
+                            );
+
+                            // Bottom accent line for active tab
+                            if is_active {
+                                ui.painter().rect_filled(
+                                    egui::Rect::from_min_size(
+                                        egui::pos2(rect.left(), rect.bottom() - 2.0),
+                                        Vec2::new(rect.width(), 2.0),
+                                    ),
+                                    Rounding::ZERO,
+                                    border_bottom,
+                                );
+                            }
+
+                            ui.allocate_ui_at_rect(rect.shrink(8.0), |ui| {
+                                ui.horizontal_centered(|ui| {
+                                    // Loading indicator or icon
+                                    if tab.is_loading {
+                                        ui.spinner();
+                                    }
+
+                                    // Title
+                                    let title = if tab.title.chars().count() > 16 {
+                                        format!("{}…", tab.title.chars().take(15).collect::<String>())
+                                    } else {
+                                        tab.title.clone()
+                                    };
+
+                                    ui.label(RichText::new(title)
                                         .color(text_color)
                                         .size(13.0));
 
@@ -424,7 +1438,7 @@ impl eframe::App for JxlApp {
                             });
                         });
 
-                        if response.response.interact(egui::Sense::click()).clicked() {
+                        if response.response.interact(egui::Sense::click()).on_hover_text(&tab.title).clicked() {
                             self.active_tab = i;
                         }
                     }
@@ -459,6 +1473,46 @@ impl eframe::App for JxlApp {
                             self.show_about = true;
                         }
 
+                        // Settings button
+                        if ui.add(
+                            egui::Button::new(RichText::new("⚙").size(13.0).color(theme::TEXT_MUTED))
+                                .frame(false)
+                                .min_size(Vec2::new(24.0, 24.0))
+                        ).on_hover_text("Settings").clicked() {
+                            self.show_settings = !self.show_settings;
+                        }
+
+                        // Recent files
+                        let mut open_recent = None;
+                        ui.add_enabled_ui(!self.recent_files.is_empty(), |ui| {
+                            ui.menu_button(RichText::new("⏷").size(13.0).color(theme::TEXT_SECONDARY), |ui| {
+                                ui.label(RichText::new("RECENT FILES")
+                                    .size(10.0)
+                                    .color(theme::TEXT_MUTED));
+                                for path in &self.recent_files {
+                                    let name = path.file_name()
+                                        .map(|n| n.to_string_lossy().to_string())
+                                        .unwrap_or_else(|| path.display().to_string());
+                                    let button = ui.add_enabled(
+                                        path.exists(),
+                                        egui::Button::new(RichText::new(name).size(12.0)).frame(false),
+                                    );
+                                    if button.on_hover_text(path.display().to_string()).clicked() {
+                                        open_recent = Some(path.clone());
+                                        ui.close_menu();
+                                    }
+                                }
+                                ui.separator();
+                                if ui.add(egui::Button::new(RichText::new("Clear").size(12.0).color(theme::TEXT_MUTED)).frame(false)).clicked() {
+                                    self.recent_files.clear();
+                                    ui.close_menu();
+                                }
+                            }).response.on_hover_text("Recent files");
+                        });
+                        if let Some(path) = open_recent {
+                            self.open_file_in_new_tab(path);
+                        }
+
                         // Open button
                         if ui.add(
                             egui::Button::new(RichText::new("Open").size(13.0).color(theme::TEXT_SECONDARY))
@@ -472,16 +1526,16 @@ impl eframe::App for JxlApp {
             });
 
         // Status bar
-        let mut toggle_anim = false;
+        let (mut export_selection, mut copy_selection) = (false, false);
         egui::TopBottomPanel::bottom("status")
             .frame(egui::Frame::none()
                 .fill(theme::BG_ELEVATED)
                 .stroke(Stroke::new(1.0, theme::BORDER_SUBTLE))
                 .inner_margin(egui::Margin::symmetric(16.0, 8.0)))
             .show_separator_line(false)
-            .show(ctx, |ui| {
+            .show_animated(ctx, !self.fullscreen, |ui| {
                 ui.horizontal(|ui| {
-                    if let Some(tab) = self.tabs.get(self.active_tab) {
+                    if let Some(tab) = self.tabs.get_mut(self.active_tab) {
                         // File info
                         if let Some((w, h)) = tab.dimensions {
                             ui.label(RichText::new(format!("{}×{}", w, h))
@@ -496,8 +1550,43 @@ impl eframe::App for JxlApp {
                                 .color(theme::TEXT_MUTED));
                         }
 
+                        if tab.file_path.is_some() {
+                            ui.label(RichText::new("•").size(12.0).color(theme::TEXT_MUTED));
+                            let color = if tab.auto_reload { theme::ACCENT } else { theme::TEXT_MUTED };
+                            if ui.add(
+                                egui::Button::new(RichText::new("⟳ Auto-reload").size(12.0).color(color))
+                                    .frame(false)
+                            ).on_hover_text("Decode the file again when it changes on disk").clicked() {
+                                tab.auto_reload = !tab.auto_reload;
+                            }
+                        }
+
+                        // Selected region, in image pixels
+                        if let Some(selection) = tab.selection {
+                            ui.label(RichText::new("•").size(12.0).color(theme::TEXT_MUTED));
+                            let coordinates = selection.to_string();
+                            if ui.add(
+                                egui::Button::new(RichText::new(format!("⛶ {}", coordinates)).size(12.0).color(theme::TEXT_SECONDARY))
+                                    .frame(false)
+                            ).on_hover_text("Copy coordinates").clicked() {
+                                ui.ctx().copy_text(coordinates);
+                            }
+                            if ui.add(
+                                egui::Button::new(RichText::new("Export PNG…").size(12.0))
+                                    .fill(theme::BG_SURFACE)
+                            ).clicked() {
+                                export_selection = true;
+                            }
+                            if ui.add(
+                                egui::Button::new(RichText::new("Copy").size(12.0))
+                                    .fill(theme::BG_SURFACE)
+                            ).on_hover_text("Copy the selected pixels as an image").clicked() {
+                                copy_selection = true;
+                            }
+                        }
+
                         // Animation controls
-                        if let Some(anim) = &tab.animation {
+                        if let Some(anim) = &mut tab.animation {
                             ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
                                 // Play/pause
                                 let icon = if anim.is_playing { "⏸" } else { "▶" };
@@ -506,23 +1595,100 @@ impl eframe::App for JxlApp {
                                         .fill(theme::BG_SURFACE)
                                         .min_size(Vec2::new(28.0, 22.0))
                                 ).clicked() {
-                                    toggle_anim = true;
+                                    anim.toggle_playing();
+                                }
+
+                                // Playback direction
+                                let (mode_icon, mode_hint) = match anim.mode {
+                                    PlaybackMode::Forward => ("→", "Forward"),
+                                    PlaybackMode::Reverse => ("←", "Reverse"),
+                                    PlaybackMode::PingPong => ("⇄", "Ping-pong"),
+                                };
+                                if ui.add(
+                                    egui::Button::new(RichText::new(mode_icon).size(12.0))
+                                        .fill(theme::BG_SURFACE)
+                                        .min_size(Vec2::new(28.0, 22.0))
+                                ).on_hover_text(mode_hint).clicked() {
+                                    anim.mode = match anim.mode {
+                                        PlaybackMode::Forward => PlaybackMode::Reverse,
+                                        PlaybackMode::Reverse => PlaybackMode::PingPong,
+                                        PlaybackMode::PingPong => PlaybackMode::Forward,
+                                    };
                                 }
 
+                                // Playback speed
+                                egui::ComboBox::from_id_salt("playback_speed")
+                                    .selected_text(RichText::new(format!("{}×", anim.speed)).size(12.0))
+                                    .width(56.0)
+                                    .show_ui(ui, |ui| {
+                                        for speed in PLAYBACK_SPEEDS {
+                                            ui.selectable_value(&mut anim.speed, speed, format!("{}×", speed));
+                                        }
+                                    });
+
                                 ui.label(RichText::new(format!("{}/{}", anim.current_frame + 1, anim.frames.len()))
                                     .size(12.0)
                                     .color(theme::TEXT_MUTED));
+
+                                ui.label(RichText::new(format!("{} / {}",
+                                        format_timestamp(anim.frame_start_ms(anim.current_frame)),
+                                        format_timestamp(anim.total_ms())))
+                                    .size(12.0)
+                                    .monospace()
+                                    .color(theme::TEXT_MUTED));
                             });
                         }
                     }
                 });
             });
 
-        if toggle_anim {
-            if let Some(tab) = self.tabs.get_mut(self.active_tab) {
-                if let Some(anim) = &mut tab.animation {
-                    anim.is_playing = !anim.is_playing;
-                }
+        if export_selection {
+            self.export_selection();
+        }
+        if copy_selection {
+            self.copy_image();
+        }
+
+        // Animation timeline
+        if let Some(anim) = self.tabs.get_mut(self.active_tab).and_then(|t| t.animation.as_mut()) {
+            egui::TopBottomPanel::bottom("timeline")
+                .frame(egui::Frame::none()
+                    .fill(theme::BG_ELEVATED)
+                    .inner_margin(egui::Margin { left: 16.0, right: 16.0, top: 8.0, bottom: 0.0 }))
+                .show_separator_line(false)
+                .show_animated(ctx, !self.fullscreen, |ui| {
+                    show_timeline(ui, anim);
+                });
+        }
+
+        // Thumbnail filmstrip
+        if self.thumbnail_view == Some(ThumbnailView::Strip) {
+            let items = self.thumbnail_items();
+            let mut source = self.thumbnail_source;
+            let mut clicked = None;
+            egui::TopBottomPanel::bottom("filmstrip")
+                .frame(egui::Frame::none()
+                    .fill(theme::BG_ELEVATED)
+                    .stroke(Stroke::new(1.0, theme::BORDER_SUBTLE))
+                    .inner_margin(egui::Margin::symmetric(16.0, 8.0)))
+                .show_separator_line(false)
+                .show_animated(ctx, !self.fullscreen, |ui| {
+                    show_thumbnail_header(ui, &mut source, items.len());
+                    ui.add_space(6.0);
+                    egui::ScrollArea::horizontal().show(ui, |ui| {
+                        ui.horizontal(|ui| {
+                            for (i, item) in items.iter().enumerate() {
+                                let is_active = item.tab == Some(self.active_tab);
+                                if thumbnail_cell(ui, &mut self.thumbnails, item, 72.0, is_active).clicked() {
+                                    clicked = Some(i);
+                                }
+                            }
+                        });
+                    });
+                });
+            self.thumbnail_source = source;
+            if let Some(item) = clicked.map(|i| &items[i]) {
+                self.open_thumbnail(item);
             }
         }
 
@@ -534,21 +1700,37 @@ impl eframe::App for JxlApp {
                 if ui.input(|i| i.key_pressed(egui::Key::O) && i.modifiers.command) {
                     self.open_file_dialog();
                 }
-                if ui.input(|i| i.key_pressed(egui::Key::T) && i.modifiers.command) {
+                if ui.input(|i| i.key_pressed(egui::Key::T) && i.modifiers.command && !i.modifiers.shift) {
                     self.tabs.push(ImageTab::new(self.next_tab_id));
                     self.next_tab_id += 1;
                     self.active_tab = self.tabs.len() - 1;
                 }
+                if ui.input(|i| i.key_pressed(egui::Key::T) && i.modifiers.command && i.modifiers.shift) {
+                    self.reopen_closed_tab();
+                }
                 if ui.input(|i| i.key_pressed(egui::Key::W) && i.modifiers.command) {
                     if self.tabs.len() > 1 {
                         self.close_tab(self.active_tab);
                     }
                 }
-                if ui.input(|i| i.key_pressed(egui::Key::Space)) {
-                    if let Some(tab) = self.tabs.get_mut(self.active_tab) {
-                        if let Some(anim) = &mut tab.animation {
-                            anim.is_playing = !anim.is_playing;
-                        }
+                if let Some(anim) = self.tabs.get_mut(self.active_tab).and_then(|t| t.animation.as_mut()) {
+                    if ui.input(|i| i.key_pressed(egui::Key::Space)) {
+                        anim.toggle_playing();
+                    }
+                    // Frame stepping pauses playback
+                    if ui.input(|i| i.key_pressed(egui::Key::ArrowRight)) {
+                        anim.is_playing = false;
+                        anim.step(1);
+                    }
+                    if ui.input(|i| i.key_pressed(egui::Key::ArrowLeft)) {
+                        anim.is_playing = false;
+                        anim.step(-1);
+                    }
+                    if ui.input(|i| i.key_pressed(egui::Key::Home)) {
+                        anim.seek(0);
+                    }
+                    if ui.input(|i| i.key_pressed(egui::Key::End)) {
+                        anim.seek(anim.frames.len().saturating_sub(1));
                     }
                 }
                 // Show about with ? key
@@ -559,34 +1741,203 @@ impl eframe::App for JxlApp {
                 if ui.input(|i| i.key_pressed(egui::Key::I)) {
                     self.show_info = !self.show_info;
                 }
-                // Escape to close dialogs
+                // Toggle layers mode with l key
+                if ui.input(|i| i.key_pressed(egui::Key::L) && !i.modifiers.command) {
+                    if let Some(tab) = self.tabs.get_mut(self.active_tab) {
+                        if tab.layers.is_some() {
+                            tab.layers = None;
+                        } else if tab.animation.is_none() && tab.file_path.is_some() {
+                            tab.start_layers(&self.settings);
+                        }
+                    }
+                }
+                // Rotate with r (shift for 180°), flip with h and v
+                if let Some(tab) = self.tabs.get_mut(self.active_tab) {
+                    let view = tab.view_orientation;
+                    ui.input(|i| {
+                        if i.modifiers.command {
+                            return;
+                        }
+                        if i.key_pressed(egui::Key::R) {
+                            tab.view_orientation = view.rotated(if i.modifiers.shift { 2 } else { 1 });
+                        }
+                        if i.key_pressed(egui::Key::H) {
+                            tab.view_orientation = view.flipped_horizontally();
+                        }
+                        if i.key_pressed(egui::Key::V) {
+                            tab.view_orientation = view.flipped_vertically();
+                        }
+                    });
+                }
+                // Show structure inspector with s key
+                if ui.input(|i| i.key_pressed(egui::Key::S) && !i.modifiers.command) {
+                    self.show_structure = !self.show_structure;
+                }
+                // Cmd+C copies the image, Cmd+Shift+C its path, Cmd+V opens the clipboard
+                // contents. The copy and paste shortcuts arrive as events rather than key
+                // presses, and Cmd+V only produces a paste event when there is text, so its
+                // key release covers clipboards holding only files or image data.
+                let (copy, copy_path, paste, paste_released) = ui.input(|i| {
+                    let copy = i.events.iter().any(|e| matches!(e, egui::Event::Copy));
+                    let paste = i.events.iter().any(|e| matches!(e, egui::Event::Paste(_)));
+                    let paste_released = i.events.iter().any(|e| matches!(e,
+                        egui::Event::Key { key: egui::Key::V, pressed: false, modifiers, .. } if modifiers.command));
+                    (copy && !i.modifiers.shift, copy && i.modifiers.shift, paste, paste_released)
+                });
+                if copy {
+                    self.copy_image();
+                }
+                if copy_path {
+                    self.copy_path();
+                }
+                if paste || (paste_released && !self.paste_handled) {
+                    self.paste();
+                }
+                if paste || paste_released {
+                    self.paste_handled = paste && !paste_released;
+                }
+                // Filmstrip with t, contact sheet with g
+                if ui.input(|i| i.key_pressed(egui::Key::T) && !i.modifiers.command) {
+                    self.thumbnail_view = match self.thumbnail_view {
+                        Some(ThumbnailView::Strip) => None,
+                        _ => Some(ThumbnailView::Strip),
+                    };
+                }
+                if ui.input(|i| i.key_pressed(egui::Key::G) && !i.modifiers.command) {
+                    self.thumbnail_view = match self.thumbnail_view {
+                        Some(ThumbnailView::Sheet) => None,
+                        _ => Some(ThumbnailView::Sheet),
+                    };
+                }
+                // Fullscreen with f or F11, slideshow with F5
+                if ui.input(|i| (i.key_pressed(egui::Key::F) && !i.modifiers.command) || i.key_pressed(egui::Key::F11)) {
+                    self.set_fullscreen(ctx, !self.fullscreen);
+                }
+                if ui.input(|i| i.key_pressed(egui::Key::F5)) {
+                    self.toggle_slideshow();
+                }
+                // Escape to close dialogs, clear the selection and leave fullscreen
                 if ui.input(|i| i.key_pressed(egui::Key::Escape)) {
+                    if let Some(tab) = self.tabs.get_mut(self.active_tab) {
+                        tab.selection = None;
+                    }
+                    if self.fullscreen {
+                        self.set_fullscreen(ctx, false);
+                    }
+                    if self.thumbnail_view == Some(ThumbnailView::Sheet) {
+                        self.thumbnail_view = None;
+                    }
+                    self.slideshow = None;
                     self.show_about = false;
+                    self.show_settings = false;
                     self.show_info = false;
+                    self.show_structure = false;
                 }
 
-                if let Some(tab) = self.tabs.get(self.active_tab) {
+                if self.thumbnail_view == Some(ThumbnailView::Sheet) {
+                    // Contact sheet in place of the image; opening an item closes it
+                    let items = self.thumbnail_items();
+                    let mut source = self.thumbnail_source;
+                    let mut clicked = None;
+                    egui::Frame::none()
+                        .inner_margin(egui::Margin::same(16.0))
+                        .show(ui, |ui| {
+                            show_thumbnail_header(ui, &mut source, items.len());
+                            ui.add_space(12.0);
+                            egui::ScrollArea::vertical().show(ui, |ui| {
+                                ui.horizontal_wrapped(|ui| {
+                                    ui.spacing_mut().item_spacing = Vec2::splat(12.0);
+                                    for (i, item) in items.iter().enumerate() {
+                                        let is_active = item.tab == Some(self.active_tab);
+                                        if thumbnail_cell(ui, &mut self.thumbnails, item, 144.0, is_active).clicked() {
+                                            clicked = Some(i);
+                                        }
+                                    }
+                                });
+                            });
+                        });
+                    self.thumbnail_source = source;
+                    if let Some(item) = clicked.map(|i| &items[i]) {
+                        self.open_thumbnail(item);
+                        self.thumbnail_view = None;
+                    }
+                } else if let Some(tab) = self.tabs.get_mut(self.active_tab) {
                     if let Some(error) = &tab.error {
                         // Error state
                         ui.centered_and_justified(|ui| {
                             ui.vertical_centered(|ui| {
                                 ui.label(RichText::new("⚠").size(48.0).color(theme::ERROR));
                                 ui.add_space(16.0);
-                                ui.label(RichText::new("Failed to load image")
+                                let heading = if tab.crashed { "Decoder crashed" } else { "Failed to load image" };
+                                ui.label(RichText::new(heading)
                                     .size(18.0)
                                     .color(theme::TEXT_PRIMARY));
                                 ui.add_space(8.0);
                                 ui.label(RichText::new(error)
                                     .size(13.0)
                                     .color(theme::TEXT_MUTED));
+                                if tab.crashed {
+                                    ui.add_space(8.0);
+                                    ui.label(RichText::new("This is a bug in the decoder, not necessarily a broken file. Please report it along with the image.")
+                                        .size(12.0)
+                                        .color(theme::TEXT_MUTED));
+                                }
                             });
                         });
-                    } else if let Some(anim) = &tab.animation {
-                        if let Some(texture) = anim.frames.get(anim.current_frame) {
-                            show_image(ui, texture);
+                    } else if let Some(cmp) = tab.compare.as_ref().filter(|c| c.view != CompareView::Image) {
+                        // Difference view against another tab
+                        match (&cmp.metrics, &cmp.diff_texture) {
+                            (Some(Err(e)), _) => {
+                                ui.centered_and_justified(|ui| {
+                                    ui.label(RichText::new(e)
+                                        .size(13.0)
+                                        .color(theme::ERROR));
+                                });
+                            }
+                            (_, Some(texture)) => show_image(ui, texture),
+                            _ => {
+                                ui.centered_and_justified(|ui| {
+                                    ui.spinner();
+                                });
+                            }
+                        }
+                    } else if let Some(layers) = &tab.layers {
+                        // Layers mode: a single layer or the composite of visible layers
+                        let texture = match layers.solo {
+                            Some(i) => layers.layers.get(i).map(|l| &l.texture),
+                            None => layers.composite.as_ref(),
+                        };
+                        if let Some(error) = &layers.error {
+                            ui.centered_and_justified(|ui| {
+                                ui.label(RichText::new(error)
+                                    .size(13.0)
+                                    .color(theme::ERROR));
+                            });
+                        } else if let Some(texture) = texture {
+                            show_image(ui, texture);
+                        } else {
+                            ui.centered_and_justified(|ui| {
+                                ui.spinner();
+                            });
+                        }
+                    } else if tab.animation.is_some() || tab.texture.is_some() {
+                        let orientation = tab.view_orientation;
+                        let mut response = None;
+                        if let Some(anim) = &mut tab.animation {
+                            for (i, frame) in anim.frames.iter_mut().enumerate() {
+                                // Keep at most one oversized frame on the GPU
+                                if i == anim.current_frame {
+                                    response = Some(show_tiled_image(ui, frame, orientation));
+                                } else if frame.is_tiled() {
+                                    frame.release();
+                                }
+                            }
+                        } else if let Some(image) = &mut tab.texture {
+                            response = Some(show_tiled_image(ui, image, orientation));
+                        }
+                        if let Some(response) = response {
+                            update_selection(ui, &response, tab);
                         }
-                    } else if let Some(texture) = &tab.texture {
-                        show_image(ui, texture);
                     } else if tab.is_loading {
                         // Loading state
                         ui.centered_and_justified(|ui| {
@@ -753,8 +2104,34 @@ impl eframe::App for JxlApp {
             }
         }
 
+        // Settings window
+        if self.show_settings {
+            let mut open = true;
+            egui::Window::new(RichText::new("Settings").size(14.0).color(theme::TEXT_PRIMARY))
+                .open(&mut open)
+                .collapsible(false)
+                .resizable(false)
+                .anchor(egui::Align2::RIGHT_TOP, Vec2::new(-16.0, 56.0))
+                .frame(egui::Frame::none()
+                    .fill(theme::BG_ELEVATED)
+                    .rounding(Rounding::same(12.0))
+                    .stroke(Stroke::new(1.0, theme::BORDER))
+                    .inner_margin(egui::Margin::same(16.0)))
+                .show(ctx, |ui| {
+                    show_settings(ui, &mut self.settings);
+                });
+            self.show_settings &= open;
+        }
+        self.save_changed_settings();
+
         // Info panel (right side)
         if self.show_info {
+            let compare_candidates: Vec<(usize, String)> = self.tabs.iter()
+                .enumerate()
+                .filter(|(i, t)| *i != self.active_tab && t.file_path.is_some())
+                .map(|(_, t)| (t.id, t.title.clone()))
+                .collect();
+
             egui::SidePanel::right("info_panel")
                 .resizable(false)
                 .default_width(280.0)
@@ -810,6 +2187,22 @@ impl eframe::App for JxlApp {
                                 .color(theme::TEXT_MUTED));
                         }
 
+                        // JPEG reconstruction data (jbrd box)
+                        if let Some(size) = tab.metadata.as_ref().and_then(|m| m.jpeg_reconstruction_size) {
+                            ui.add_space(8.0);
+                            ui.label(RichText::new("Losslessly recompressed JPEG")
+                                .size(12.0)
+                                .color(theme::SUCCESS));
+                            ui.label(RichText::new(format!("Reconstruction data: {} bytes", size))
+                                .size(11.0)
+                                .color(theme::TEXT_MUTED));
+                            ui.add_enabled(
+                                false,
+                                egui::Button::new(RichText::new("Export original JPEG").size(12.0))
+                                    .fill(theme::BG_SURFACE)
+                            ).on_disabled_hover_text("JPEG reconstruction is not supported by jxl-rs yet");
+                        }
+
                         ui.add_space(16.0);
 
                         // Dimensions section
@@ -827,6 +2220,18 @@ impl eframe::App for JxlApp {
                                 .size(11.0)
                                 .color(theme::TEXT_MUTED));
 
+                            let image = tab.animation.as_ref()
+                                .and_then(|a| a.frames.get(a.current_frame))
+                                .or(tab.texture.as_ref());
+                            if let Some(image) = image.filter(|i| i.is_tiled()) {
+                                ui.label(RichText::new(format!(
+                                    "Tiled · {:.1} MB on GPU",
+                                    image.resident_bytes() as f64 / 1_048_576.0
+                                ))
+                                    .size(11.0)
+                                    .color(theme::TEXT_MUTED));
+                            }
+
                             ui.add_space(16.0);
                         }
 
@@ -846,6 +2251,18 @@ impl eframe::App for JxlApp {
                                     .color(theme::ACCENT));
                             });
 
+                            if let Some(conversion) = tab.conversion_time {
+                                ui.horizontal(|ui| {
+                                    ui.label(RichText::new("Conversion time:")
+                                        .size(12.0)
+                                        .color(theme::TEXT_SECONDARY));
+                                    ui.label(RichText::new(format!("{:.1} ms", conversion.as_secs_f64() * 1000.0))
+                                        .size(12.0)
+                                        .color(theme::ACCENT))
+                                        .on_hover_text("Planar float to RGBA8, after decoding");
+                                });
+                            }
+
                             // Calculate decode speed
                             if let Some((w, h)) = tab.dimensions {
                                 let pixels = w as f64 * h as f64;
@@ -863,6 +2280,11 @@ impl eframe::App for JxlApp {
                             ui.label(RichText::new("Decoding...")
                                 .size(12.0)
                                 .color(theme::TEXT_MUTED));
+                            if tab.texture.as_ref().is_some_and(|t| t.is_preview()) {
+                                ui.label(RichText::new("Showing the embedded preview")
+                                    .size(11.0)
+                                    .color(theme::TEXT_MUTED));
+                            }
                         } else {
                             ui.label(RichText::new("No data")
                                 .size(12.0)
@@ -905,12 +2327,112 @@ impl eframe::App for JxlApp {
                                     .size(12.0)
                                     .color(if anim.is_playing { theme::SUCCESS } else { theme::TEXT_MUTED }));
                             });
+
+                            ui.horizontal(|ui| {
+                                ui.label(RichText::new("Loops:")
+                                    .size(12.0)
+                                    .color(theme::TEXT_SECONDARY));
+                                let loops = if anim.loop_count == 0 {
+                                    "∞".to_string()
+                                } else {
+                                    format!("{} / {}", anim.loops_completed.min(anim.loop_count), anim.loop_count)
+                                };
+                                ui.label(RichText::new(loops)
+                                    .size(12.0)
+                                    .color(theme::TEXT_PRIMARY));
+                            });
+
+                            if let Some(rate) = tab.image_info.as_ref().and_then(|i| i.tick_rate) {
+                                ui.horizontal(|ui| {
+                                    ui.label(RichText::new("Tick rate:")
+                                        .size(12.0)
+                                        .color(theme::TEXT_SECONDARY));
+                                    ui.label(RichText::new(format!("{}/{} ({:.3} tps)",
+                                            rate.numerator,
+                                            rate.denominator,
+                                            rate.ticks_per_second()))
+                                        .size(12.0)
+                                        .color(theme::TEXT_PRIMARY));
+                                });
+                            }
+
+                            if tab.image_info.as_ref().is_some_and(|i| i.have_timecodes) {
+                                ui.horizontal(|ui| {
+                                    ui.label(RichText::new("Timecodes:")
+                                        .size(12.0)
+                                        .color(theme::TEXT_SECONDARY));
+                                    ui.label(RichText::new("present")
+                                        .size(12.0)
+                                        .color(theme::TEXT_PRIMARY))
+                                        .on_hover_text("Per-frame timecode values are not exposed by jxl-rs");
+                                });
+                            }
+                        }
+
+                        // Container metadata
+                        if let Some(metadata) = tab.metadata.as_ref().filter(|m| !m.is_empty()) {
+                            ui.add_space(16.0);
+                            show_metadata_section(ui, metadata);
                         }
                     } else {
                         ui.label(RichText::new("No image loaded")
                             .size(13.0)
                             .color(theme::TEXT_MUTED));
                     }
+
+                    if let Some(tab) = self.tabs.get_mut(self.active_tab) {
+                        if tab.file_path.is_some() {
+                            show_orientation_section(ui, tab, &self.settings);
+                            show_layers_section(ui, tab, &self.settings);
+                            show_compare_section(ui, tab, &compare_candidates);
+                        }
+                    }
+                });
+        }
+
+        // Structure inspector (left side)
+        if self.show_structure {
+            egui::SidePanel::left("structure_panel")
+                .resizable(true)
+                .default_width(320.0)
+                .frame(egui::Frame::none()
+                    .fill(theme::BG_ELEVATED)
+                    .stroke(Stroke::new(1.0, theme::BORDER))
+                    .inner_margin(egui::Margin::same(16.0)))
+                .show(ctx, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.label(RichText::new("Structure")
+                            .size(14.0)
+                            .color(theme::TEXT_PRIMARY)
+                            .strong());
+                        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                            if ui.add(
+                                egui::Button::new(RichText::new("✕").size(14.0).color(theme::TEXT_MUTED))
+                                    .frame(false)
+                            ).clicked() {
+                                self.show_structure = false;
+                            }
+                        });
+                    });
+                    ui.add_space(16.0);
+
+                    match self.tabs.get(self.active_tab).and_then(|t| t.structure.as_ref()) {
+                        Some(Ok(structure)) => {
+                            egui::ScrollArea::vertical().show(ui, |ui| {
+                                show_structure(ui, structure);
+                            });
+                        }
+                        Some(Err(e)) => {
+                            ui.label(RichText::new(e)
+                                .size(12.0)
+                                .color(theme::ERROR));
+                        }
+                        None => {
+                            ui.label(RichText::new("No image loaded")
+                                .size(13.0)
+                                .color(theme::TEXT_MUTED));
+                        }
+                    }
                 });
         }
 
@@ -930,11 +2452,252 @@ impl eframe::App for JxlApp {
     }
 }
 
-fn show_image(ui: &mut egui::Ui, texture: &egui::TextureHandle) {
+/// Timeline strip with one segment per frame, sized by frame duration.
+/// Click or drag to scrub.
+fn show_timeline(ui: &mut egui::Ui, anim: &mut AnimationState) {
+    let (rect, response) = ui.allocate_exact_size(
+        Vec2::new(ui.available_width(), 24.0),
+        egui::Sense::click_and_drag(),
+    );
+    let painter = ui.painter_at(rect);
+    let total = anim.total_ms().max(1.0) as f32;
+
+    painter.rect_filled(rect, Rounding::same(4.0), theme::BG_SURFACE);
+
+    let mut start = 0.0;
+    for (i, &duration) in anim.durations.iter().enumerate() {
+        let x0 = rect.left() + rect.width() * start / total;
+        start += duration as f32;
+        let x1 = rect.left() + rect.width() * start / total;
+
+        let segment = egui::Rect::from_x_y_ranges(x0..=x1, rect.y_range());
+        let fill = if i == anim.current_frame {
+            theme::ACCENT
+        } else if i < anim.current_frame {
+            theme::BG_ACTIVE
+        } else {
+            theme::BG_HOVER
+        };
+        painter.rect_filled(segment.shrink2(Vec2::new(0.5, 0.0)), Rounding::ZERO, fill);
+    }
+
+    if let Some(pos) = response.interact_pointer_pos() {
+        if response.clicked() || response.dragged() {
+            let t = ((pos.x - rect.left()) / rect.width()).clamp(0.0, 1.0) * total;
+            anim.seek(anim.frame_at_ms(t as f64));
+        }
+    }
+
+    response.on_hover_text("Click or drag to scrub");
+}
+
+/// Format milliseconds as `m:ss.mmm`
+fn format_timestamp(ms: f64) -> String {
+    let ms = ms.round() as u64;
+    format!("{}:{:02}.{:03}", ms / 60_000, (ms / 1000) % 60, ms % 1000)
+}
+
+fn show_settings(ui: &mut egui::Ui, settings: &mut Settings) {
+    ui.label(RichText::new("MEMORY")
+        .size(10.0)
+        .color(theme::TEXT_MUTED));
+    ui.add_space(4.0);
+
+    egui::Grid::new("settings_memory")
+        .num_columns(2)
+        .spacing(Vec2::new(12.0, 6.0))
+        .show(ui, |ui| {
+            ui.label(RichText::new("Memory budget:")
+                .size(12.0)
+                .color(theme::TEXT_SECONDARY));
+            ui.add(egui::DragValue::new(&mut settings.memory_budget_mb)
+                .range(64..=65536)
+                .speed(16)
+                .suffix(" MB"))
+                .on_hover_text("Textures and decoded pixels of all tabs. Above this, inactive tabs are released and decoded again when shown, then distant animation frames");
+            ui.end_row();
+
+            ui.label(RichText::new("Pixel limit:")
+                .size(12.0)
+                .color(theme::TEXT_SECONDARY));
+            ui.add(egui::DragValue::new(&mut settings.pixel_limit_mp)
+                .range(0..=10000)
+                .speed(4)
+                .suffix(" MP"))
+                .on_hover_text("Larger images are refused before decoding; 0 disables the limit");
+            ui.end_row();
+        });
+
+    ui.add_space(16.0);
+    ui.label(RichText::new("DECODING")
+        .size(10.0)
+        .color(theme::TEXT_MUTED));
+    ui.add_space(4.0);
+    ui.horizontal(|ui| {
+        ui.label(RichText::new("Conversion threads:")
+            .size(12.0)
+            .color(theme::TEXT_SECONDARY));
+        let cores = thread::available_parallelism().map_or(1, |n| n.get());
+        ui.add(egui::DragValue::new(&mut settings.decoder_threads)
+            .range(0..=1024)
+            .custom_formatter(|n, _| if n == 0.0 { "Auto".to_string() } else { format!("{}", n) }))
+            .on_hover_text(format!("Threads for converting decoded pixels to RGBA; the decoder itself uses one. Auto uses all {} cores. Applies to images opened afterwards", cores));
+    });
+
+    ui.add_space(16.0);
+    ui.label(RichText::new("PLAYBACK")
+        .size(10.0)
+        .color(theme::TEXT_MUTED));
+    ui.add_space(4.0);
+    ui.checkbox(&mut settings.autoplay, RichText::new("Play animations when opened")
+        .size(12.0)
+        .color(theme::TEXT_SECONDARY));
+    ui.add_space(4.0);
+    ui.horizontal(|ui| {
+        ui.label(RichText::new("Slideshow interval:")
+            .size(12.0)
+            .color(theme::TEXT_SECONDARY));
+        ui.add(egui::DragValue::new(&mut settings.slideshow_interval_secs)
+            .range(0.5..=3600.0)
+            .speed(0.1)
+            .suffix(" s"))
+            .on_hover_text("Animations finish the loop they are in before moving on");
+    });
+}
+
+/// Fit an image of `img_size` into the available space without upscaling
+/// Title row of the filmstrip and contact sheet, with the choice of what to show
+fn show_thumbnail_header(ui: &mut egui::Ui, source: &mut ThumbnailSource, count: usize) {
+    ui.horizontal(|ui| {
+        ui.label(RichText::new("THUMBNAILS")
+            .size(10.0)
+            .color(theme::TEXT_MUTED));
+        ui.add_space(8.0);
+        ui.selectable_value(source, ThumbnailSource::Tabs, RichText::new("Open tabs").size(12.0));
+        ui.selectable_value(source, ThumbnailSource::Folder, RichText::new("Folder").size(12.0))
+            .on_hover_text("JXL files in the folder of the current image");
+        ui.label(RichText::new(format!("{} images", count))
+            .size(12.0)
+            .color(theme::TEXT_SECONDARY));
+    });
+}
+
+/// A square thumbnail with the file name below it
+fn thumbnail_cell(
+    ui: &mut egui::Ui,
+    thumbnails: &mut thumbnails::Thumbnails,
+    item: &ThumbnailItem,
+    size: f32,
+    is_active: bool,
+) -> egui::Response {
+    let (rect, response) = ui.allocate_exact_size(Vec2::new(size, size + 18.0), egui::Sense::click());
+    // Off-screen cells are not requested, so long folders load as they are scrolled into view
+    if ui.is_rect_visible(rect) {
+        let image_rect = egui::Rect::from_min_size(rect.min, Vec2::splat(size));
+        let painter = ui.painter();
+        let bg = if response.hovered() { theme::BG_HOVER } else { theme::BG_SURFACE };
+        painter.rect_filled(image_rect, Rounding::same(6.0), bg);
+
+        match thumbnails.get(&item.path) {
+            Some(Ok(texture)) => {
+                let inner = image_rect.shrink(4.0);
+                let texture_size = texture.size_vec2();
+                let scale = (inner.width() / texture_size.x).min(inner.height() / texture_size.y);
+                painter.image(
+                    texture.id(),
+                    egui::Rect::from_center_size(inner.center(), texture_size * scale),
+                    egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0)),
+                    Color32::WHITE,
+                );
+            }
+            Some(Err(_)) => {
+                painter.text(image_rect.center(), egui::Align2::CENTER_CENTER, "⚠",
+                    egui::FontId::proportional(20.0), theme::ERROR);
+            }
+            None => {
+                painter.text(image_rect.center(), egui::Align2::CENTER_CENTER, "…",
+                    egui::FontId::proportional(16.0), theme::TEXT_MUTED);
+            }
+        }
+        if is_active {
+            painter.rect_stroke(image_rect, Rounding::same(6.0), Stroke::new(2.0, theme::ACCENT));
+        }
+
+        let max_chars = (size / 7.0) as usize;
+        let label = if item.label.chars().count() > max_chars {
+            format!("{}…", item.label.chars().take(max_chars.saturating_sub(1)).collect::<String>())
+        } else {
+            item.label.clone()
+        };
+        let color = if is_active { theme::TEXT_PRIMARY } else { theme::TEXT_SECONDARY };
+        painter.text(egui::pos2(rect.center().x, image_rect.bottom() + 4.0), egui::Align2::CENTER_TOP,
+            label, egui::FontId::proportional(11.0), color);
+    }
+
+    if !response.hovered() {
+        return response;
+    }
+    let hover = match thumbnails.get(&item.path) {
+        Some(Err(e)) => format!("{}\n{}", item.path.display(), e),
+        _ => item.path.display().to_string(),
+    };
+    response.on_hover_text(hover)
+}
+
+fn fit_size(ui: &egui::Ui, img_size: Vec2) -> Vec2 {
     let available = ui.available_size();
-    let img_size = texture.size_vec2();
     let scale = (available.x / img_size.x).min(available.y / img_size.y).min(1.0);
-    let size = img_size * scale;
+    img_size * scale
+}
+
+/// Like [`show_image`], for images that may exceed the texture size limit,
+/// rotated and flipped by `orientation`
+fn show_tiled_image(ui: &mut egui::Ui, image: &mut tiles::TiledImage, orientation: Orientation) -> egui::Response {
+    let size = fit_size(ui, orientation.apply_to_size(image.size_vec2()));
+
+    ui.centered_and_justified(|ui| {
+        let (rect, response) = ui.allocate_exact_size(size, egui::Sense::click_and_drag());
+        image.paint(ui, rect, orientation);
+        response
+    }).inner
+}
+
+/// Drag on the image to select a region, click to clear it; draws the selection
+fn update_selection(ui: &egui::Ui, response: &egui::Response, tab: &mut ImageTab) {
+    let Some(dims) = tab.dimensions else {
+        return;
+    };
+    let rect = response.rect;
+    let orientation = tab.view_orientation;
+    let to_image = |pos: egui::Pos2| orientation.unapply(((pos - rect.min) / rect.size()).to_pos2());
+    let to_screen = |point: egui::Pos2| rect.min + orientation.apply(point).to_vec2() * rect.size();
+
+    if response.drag_started() {
+        tab.selection_start = response.interact_pointer_pos().map(to_image);
+    }
+    if response.dragged() {
+        if let (Some(start), Some(pos)) = (tab.selection_start, response.interact_pointer_pos()) {
+            tab.selection = crop::Selection::from_points(start, to_image(pos), dims);
+        }
+    }
+    if response.clicked() {
+        tab.selection = None;
+    }
+    if response.hovered() {
+        ui.ctx().set_cursor_icon(egui::CursorIcon::Crosshair);
+    }
+
+    if let Some(selection) = tab.selection.filter(|s| s.fits(dims)) {
+        let (min, max) = selection.normalized(dims);
+        let selected = egui::Rect::from_two_pos(to_screen(min), to_screen(max));
+        let painter = ui.painter_at(rect);
+        painter.rect_filled(selected, Rounding::ZERO, theme::ACCENT.gamma_multiply(0.15));
+        painter.rect_stroke(selected, Rounding::ZERO, Stroke::new(1.5, theme::ACCENT));
+    }
+}
+
+fn show_image(ui: &mut egui::Ui, texture: &egui::TextureHandle) {
+    let size = fit_size(ui, texture.size_vec2());
 
     ui.centered_and_justified(|ui| {
         ui.add(egui::Image::new((texture.id(), size))
@@ -942,11 +2705,536 @@ fn show_image(ui: &mut egui::Ui, texture: &egui::TextureHandle) {
     });
 }
 
-fn decode_file(path: PathBuf, tx: Sender<DecoderMessage>) {
-    match decoder::worker::decode_jxl(&path) {
+fn show_orientation_section(ui: &mut egui::Ui, tab: &mut ImageTab, settings: &Settings) {
+    let Some(header) = tab.image_info.as_ref().map(|i| i.orientation) else {
+        return;
+    };
+
+    ui.add_space(16.0);
+    ui.label(RichText::new("ORIENTATION")
+        .size(10.0)
+        .color(theme::TEXT_MUTED));
+    ui.add_space(4.0);
+
+    let header_text = Orientation::from_exif(header)
+        .map(|o| format!("{} · {}", header, o.label()))
+        .unwrap_or_else(|| header.to_string());
+    ui.horizontal(|ui| {
+        ui.label(RichText::new("Header:")
+            .size(12.0)
+            .color(theme::TEXT_SECONDARY));
+        ui.label(RichText::new(header_text)
+            .size(12.0)
+            .color(theme::TEXT_PRIMARY));
+    });
+
+    if let Some(exif) = tab.metadata.as_ref().and_then(|m| m.exif_orientation) {
+        ui.horizontal(|ui| {
+            ui.label(RichText::new("Exif:")
+                .size(12.0)
+                .color(theme::TEXT_SECONDARY));
+            let color = if exif == header { theme::TEXT_PRIMARY } else { theme::WARNING };
+            ui.label(RichText::new(exif.to_string())
+                .size(12.0)
+                .color(color))
+                .on_hover_text("JPEG XL ignores the Exif orientation; the header value applies");
+        });
+    }
+
+    ui.horizontal(|ui| {
+        ui.label(RichText::new("View:")
+            .size(12.0)
+            .color(theme::TEXT_SECONDARY));
+        ui.label(RichText::new(tab.view_orientation.label())
+            .size(12.0)
+            .color(theme::TEXT_PRIMARY));
+    });
+
+    ui.add_space(4.0);
+    ui.horizontal(|ui| {
+        let view = tab.view_orientation;
+        let buttons = [
+            ("↻ 90°", "Rotate clockwise (R)", view.rotated(1)),
+            ("180°", "Rotate 180° (Shift+R)", view.rotated(2)),
+            ("Flip H", "Mirror left to right (H)", view.flipped_horizontally()),
+            ("Flip V", "Mirror top to bottom (V)", view.flipped_vertically()),
+        ];
+        for (label, hint, orientation) in buttons {
+            if ui.add(egui::Button::new(RichText::new(label).size(12.0)).fill(theme::BG_SURFACE))
+                .on_hover_text(hint)
+                .clicked()
+            {
+                tab.view_orientation = orientation;
+            }
+        }
+        if ui.add_enabled(!view.is_identity(), egui::Button::new(RichText::new("Reset").size(12.0)).frame(false))
+            .clicked()
+        {
+            tab.view_orientation = Orientation::IDENTITY;
+        }
+    });
+
+    if ui.checkbox(&mut tab.raw_orientation, RichText::new("Show as stored").size(12.0).color(theme::TEXT_SECONDARY))
+        .on_hover_text("Decode without applying the header orientation")
+        .changed()
+    {
+        // Layers are decoded again too, so they match the image
+        let had_layers = tab.layers.is_some();
+        tab.reload(settings);
+        if had_layers {
+            tab.start_layers(settings);
+        }
+    }
+}
+
+fn show_layers_section(ui: &mut egui::Ui, tab: &mut ImageTab, settings: &Settings) {
+    // Only offered for still images made of more than one frame
+    let frame_count = tab.structure.as_ref()
+        .and_then(|s| s.as_ref().ok())
+        .map(|s| s.frames.len())
+        .unwrap_or(0);
+    if tab.animation.is_some() || (frame_count < 2 && tab.layers.is_none()) {
+        return;
+    }
+
+    ui.add_space(16.0);
+    ui.horizontal(|ui| {
+        ui.label(RichText::new("LAYERS")
+            .size(10.0)
+            .color(theme::TEXT_MUTED));
+        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+            let label = if tab.layers.is_some() { "Flatten" } else { "Browse" };
+            if ui.add(
+                egui::Button::new(RichText::new(label).size(11.0).color(theme::TEXT_SECONDARY))
+                    .fill(theme::BG_SURFACE)
+            ).clicked() {
+                if tab.layers.is_some() {
+                    tab.layers = None;
+                } else {
+                    tab.start_layers(settings);
+                }
+            }
+        });
+    });
+    ui.add_space(4.0);
+
+    let Some(state) = &mut tab.layers else {
+        ui.label(RichText::new(format!("{} frames in codestream", frame_count))
+            .size(12.0)
+            .color(theme::TEXT_SECONDARY));
+        return;
+    };
+
+    if state.rx.is_some() {
+        ui.label(RichText::new("Decoding layers...")
+            .size(12.0)
+            .color(theme::TEXT_MUTED));
+        return;
+    }
+
+    let mut solo = state.solo;
+    for (i, layer) in state.layers.iter_mut().enumerate() {
+        ui.horizontal(|ui| {
+            if ui.checkbox(&mut layer.visible, "").changed() {
+                state.composite_dirty = true;
+            }
+
+            let name = if layer.name.is_empty() {
+                format!("Layer {}", i + 1)
+            } else {
+                layer.name.clone()
+            };
+            let is_solo = solo == Some(i);
+            if ui.selectable_label(is_solo, RichText::new(name).size(12.0))
+                .on_hover_text("View this layer alone")
+                .clicked()
+            {
+                solo = if is_solo { None } else { Some(i) };
+            }
+
+            let crop = if layer.is_cropped { " • cropped, not composited" } else { "" };
+            ui.label(RichText::new(format!("{}×{}{}", layer.size.0, layer.size.1, crop))
+                .size(11.0)
+                .color(theme::TEXT_MUTED));
+        });
+    }
+    state.solo = solo;
+
+    if state.layers.iter().any(|l| l.is_cropped) {
+        ui.label(RichText::new("Crop offsets and blend modes are not exposed by jxl-rs, \
+                                so cropped layers can only be viewed on their own.")
+            .size(11.0)
+            .color(theme::TEXT_MUTED));
+    }
+}
+
+fn show_compare_section(ui: &mut egui::Ui, tab: &mut ImageTab, candidates: &[(usize, String)]) {
+    ui.add_space(16.0);
+    ui.label(RichText::new("COMPARE")
+        .size(10.0)
+        .color(theme::TEXT_MUTED));
+    ui.add_space(4.0);
+
+    if candidates.is_empty() {
+        ui.label(RichText::new("Open another image to compare")
+            .size(12.0)
+            .color(theme::TEXT_MUTED));
+        return;
+    }
+
+    let current = tab.compare.as_ref().map(|c| c.other_tab_id);
+    let mut selected = current;
+    let selected_text = selected
+        .and_then(|id| candidates.iter().find(|(i, _)| *i == id))
+        .map(|(_, title)| title.clone())
+        .unwrap_or_else(|| "None".to_string());
+
+    egui::ComboBox::from_id_salt("compare_with")
+        .selected_text(selected_text)
+        .width(ui.available_width())
+        .show_ui(ui, |ui| {
+            ui.selectable_value(&mut selected, None, "None");
+            for (id, title) in candidates {
+                ui.selectable_value(&mut selected, Some(*id), title);
+            }
+        });
+
+    if selected != current {
+        tab.compare = selected.map(CompareState::new);
+    }
+
+    let Some(cmp) = &mut tab.compare else {
+        return;
+    };
+
+    ui.add_space(4.0);
+    ui.horizontal(|ui| {
+        for (view, label) in [
+            (CompareView::Image, "Image"),
+            (CompareView::Difference, "Difference"),
+            (CompareView::Heatmap, "Heatmap"),
+        ] {
+            if ui.selectable_value(&mut cmp.view, view, label).changed() {
+                cmp.diff_dirty = true;
+            }
+        }
+    });
+
+    if cmp.view != CompareView::Image {
+        let slider = egui::Slider::new(&mut cmp.amplification, 1.0..=64.0)
+            .logarithmic(true)
+            .suffix("×")
+            .text("Amplify");
+        if ui.add(slider).changed() {
+            cmp.diff_dirty = true;
+        }
+    }
+
+    ui.add_space(4.0);
+
+    let metric_row = |ui: &mut egui::Ui, label: &str, value: String| {
+        ui.horizontal(|ui| {
+            ui.label(RichText::new(label)
+                .size(12.0)
+                .color(theme::TEXT_SECONDARY));
+            ui.label(RichText::new(value)
+                .size(12.0)
+                .color(theme::ACCENT));
+        });
+    };
+
+    match &cmp.metrics {
+        Some(Ok(metrics)) => {
+            let psnr = if metrics.psnr.is_infinite() {
+                "∞ dB".to_string()
+            } else {
+                format!("{:.2} dB", metrics.psnr)
+            };
+            metric_row(ui, "PSNR:", psnr);
+            metric_row(ui, "SSIM:", format!("{:.5}", metrics.ssim));
+            metric_row(ui, "XYB max:", format!("{:.3}", metrics.xyb_max));
+            metric_row(ui, "XYB 3-norm:", format!("{:.3}", metrics.xyb_pnorm));
+        }
+        Some(Err(e)) => {
+            ui.label(RichText::new(e)
+                .size(12.0)
+                .color(theme::ERROR));
+        }
+        None => {
+            ui.label(RichText::new("Computing...")
+                .size(12.0)
+                .color(theme::TEXT_MUTED));
+        }
+    }
+}
+
+fn show_metadata_section(ui: &mut egui::Ui, metadata: &decoder::metadata::ContainerMetadata) {
+    egui::CollapsingHeader::new(RichText::new("METADATA")
+            .size(10.0)
+            .color(theme::TEXT_MUTED))
+        .default_open(false)
+        .show(ui, |ui| {
+            if let Some(exif) = &metadata.exif {
+                match exif {
+                    Ok(tags) => {
+                        let text: String = tags.iter()
+                            .map(|t| format!("[{}] {}: {}\n", t.ifd, t.name, t.value))
+                            .collect();
+                        metadata_heading(ui, "Exif", &text);
+                        egui::ScrollArea::vertical()
+                            .id_salt("exif_scroll")
+                            .max_height(200.0)
+                            .show(ui, |ui| {
+                                egui::Grid::new("exif_grid")
+                                    .num_columns(2)
+                                    .striped(true)
+                                    .show(ui, |ui| {
+                                        for tag in tags {
+                                            ui.label(RichText::new(&tag.name)
+                                                .size(11.0)
+                                                .color(theme::TEXT_SECONDARY));
+                                            ui.label(RichText::new(&tag.value)
+                                                .size(11.0)
+                                                .color(theme::TEXT_PRIMARY));
+                                            ui.end_row();
+                                        }
+                                    });
+                            });
+                    }
+                    Err(e) => {
+                        metadata_heading(ui, "Exif", e);
+                        ui.label(RichText::new(e)
+                            .size(11.0)
+                            .color(theme::ERROR));
+                    }
+                }
+                ui.add_space(8.0);
+            }
+
+            if let Some(xmp) = &metadata.xmp {
+                metadata_heading(ui, "XMP", xmp);
+                egui::ScrollArea::both()
+                    .id_salt("xmp_scroll")
+                    .max_height(240.0)
+                    .show(ui, |ui| {
+                        ui.label(RichText::new(xmp)
+                            .size(11.0)
+                            .monospace()
+                            .color(theme::TEXT_PRIMARY));
+                    });
+                ui.add_space(8.0);
+            }
+
+            if !metadata.jumbf.is_empty() {
+                let text: String = metadata.jumbf.iter()
+                    .map(|j| format!("{} ({} bytes): {}\n",
+                        j.label.as_deref().unwrap_or("(unlabeled)"),
+                        j.size,
+                        j.content_types.join(", ")))
+                    .collect();
+                metadata_heading(ui, "JUMBF", &text);
+                for entry in &metadata.jumbf {
+                    ui.label(RichText::new(format!("{} • {} bytes",
+                            entry.label.as_deref().unwrap_or("(unlabeled)"),
+                            entry.size))
+                        .size(11.0)
+                        .color(theme::TEXT_PRIMARY));
+                    if !entry.content_types.is_empty() {
+                        ui.label(RichText::new(entry.content_types.join(", "))
+                            .size(11.0)
+                            .color(theme::TEXT_MUTED));
+                    }
+                }
+            }
+        });
+}
+
+/// Sub-heading inside the metadata section with a copy-to-clipboard button
+fn metadata_heading(ui: &mut egui::Ui, title: &str, copy_text: &str) {
+    ui.horizontal(|ui| {
+        ui.label(RichText::new(title)
+            .size(12.0)
+            .color(theme::TEXT_SECONDARY)
+            .strong());
+        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+            if ui.add(
+                egui::Button::new(RichText::new("Copy").size(11.0).color(theme::TEXT_MUTED))
+                    .fill(theme::BG_SURFACE)
+            ).clicked() {
+                ui.ctx().copy_text(copy_text.to_string());
+            }
+        });
+    });
+}
+
+fn show_structure(ui: &mut egui::Ui, structure: &decoder::structure::FileStructure) {
+    use decoder::container::FileKind;
+
+    let section = |ui: &mut egui::Ui, title: &str| {
+        ui.label(RichText::new(title)
+            .size(10.0)
+            .color(theme::TEXT_MUTED));
+        ui.add_space(4.0);
+    };
+    let row = |ui: &mut egui::Ui, label: &str, value: String| {
+        ui.horizontal(|ui| {
+            ui.label(RichText::new(label)
+                .size(12.0)
+                .color(theme::TEXT_SECONDARY));
+            ui.label(RichText::new(value)
+                .size(12.0)
+                .color(theme::TEXT_PRIMARY));
+        });
+    };
+
+    section(ui, "FILE");
+    let kind = match structure.kind {
+        FileKind::Codestream => "Bare codestream",
+        FileKind::Container => "ISOBMFF container",
+        FileKind::Unknown => "Unknown",
+    };
+    row(ui, "Layout:", kind.to_string());
+    row(ui, "File size:", format!("{} bytes", structure.file_size));
+    row(ui, "Codestream:", format!("{} bytes", structure.codestream_size));
+    if let Some((w, h)) = structure.image_size {
+        row(ui, "Canvas:", format!("{} × {}", w, h));
+    }
+    ui.add_space(16.0);
+
+    if !structure.boxes.is_empty() {
+        section(ui, "BOXES");
+        egui::Grid::new("box_grid")
+            .num_columns(3)
+            .striped(true)
+            .show(ui, |ui| {
+                for jxl_box in &structure.boxes {
+                    box_row(ui, jxl_box, 0);
+                }
+            });
+        ui.add_space(16.0);
+    }
+
+    if !structure.partial_codestreams.is_empty() {
+        section(ui, "PARTIAL CODESTREAM (jxlp)");
+        for part in &structure.partial_codestreams {
+            row(ui, &format!("#{}:", part.index), format!("@{} • {} bytes{}",
+                part.offset,
+                part.size,
+                if part.is_last { " • last" } else { "" }));
+        }
+        ui.add_space(16.0);
+    }
+
+    section(ui, "FRAMES");
+    egui::Grid::new("frame_grid")
+        .num_columns(4)
+        .striped(true)
+        .show(ui, |ui| {
+            for (i, frame) in structure.frames.iter().enumerate() {
+                let cell = |ui: &mut egui::Ui, text: String| {
+                    ui.label(RichText::new(text)
+                        .size(11.0)
+                        .color(theme::TEXT_PRIMARY));
+                };
+                cell(ui, format!("#{}", i));
+                cell(ui, if frame.name.is_empty() { "—".to_string() } else { frame.name.clone() });
+                cell(ui, frame.duration
+                    .map(|d| format!("{} ticks • {:.1} ms", d.ticks, d.ms))
+                    .unwrap_or_else(|| "—".to_string()));
+                cell(ui, format!("{}×{}{}",
+                    frame.size.0,
+                    frame.size.1,
+                    if frame.is_cropped { " (crop)" } else { "" }));
+                ui.end_row();
+            }
+        });
+
+    if let Some(e) = &structure.frames_error {
+        ui.add_space(4.0);
+        ui.label(RichText::new(e)
+            .size(11.0)
+            .color(theme::ERROR));
+    }
+}
+
+fn box_row(ui: &mut egui::Ui, jxl_box: &decoder::container::JxlBox, depth: usize) {
+    let color = if jxl_box.truncated { theme::ERROR } else { theme::TEXT_PRIMARY };
+    ui.label(RichText::new(format!("{}{}", "  ".repeat(depth), jxl_box.type_str()))
+        .size(11.0)
+        .monospace()
+        .color(color));
+    ui.label(RichText::new(format!("@{}", jxl_box.offset))
+        .size(11.0)
+        .color(theme::TEXT_MUTED));
+    ui.label(RichText::new(format!("{} bytes{}",
+            jxl_box.size,
+            if jxl_box.truncated { " (truncated)" } else { "" }))
+        .size(11.0)
+        .color(theme::TEXT_SECONDARY));
+    ui.end_row();
+
+    for child in &jxl_box.children {
+        box_row(ui, child, depth + 1);
+    }
+}
+
+/// Alpha-composite layers (straight alpha) in order onto a transparent canvas
+fn composite_layers(layers: &[&Layer], width: u32, height: u32) -> Vec<u8> {
+    let mut canvas = vec![0u8; width as usize * height as usize * 4];
+
+    // Only layers covering the whole canvas are blended, so pixels line up
+    for layer in layers.iter().filter(|l| l.size == (width, height)) {
+        for (src, dst) in layer.rgba.chunks_exact(4).zip(canvas.chunks_exact_mut(4)) {
+            let sa = src[3] as f32 / 255.0;
+            let da = dst[3] as f32 / 255.0;
+            let out_a = sa + da * (1.0 - sa);
+            if out_a > 0.0 {
+                for c in 0..3 {
+                    let v = (src[c] as f32 * sa + dst[c] as f32 * da * (1.0 - sa)) / out_a;
+                    dst[c] = v.round() as u8;
+                }
+            }
+            dst[3] = (out_a * 255.0).round() as u8;
+        }
+    }
+
+    canvas
+}
+
+fn decode_file(path: PathBuf, options: decoder::worker::DecodeOptions, tx: Sender<DecoderMessage>) {
+    match decoder::metadata::read_metadata_from_file(&path) {
+        Ok(metadata) => {
+            let _ = tx.send(DecoderMessage::Metadata(metadata));
+        }
+        Err(e) => log::warn!("Failed to read container metadata: {}", e),
+    }
+
+    let structure = decoder::worker::catch_panic(|| decoder::structure::inspect_file(&path))
+        .map_err(|e| e.to_string());
+    let _ = tx.send(DecoderMessage::Structure(structure));
+
+    // The full decode starts right away rather than waiting for the preview
+    let (preview_path, preview_options, preview_tx) = (path.clone(), options.clone(), tx.clone());
+    thread::spawn(move || {
+        match decoder::worker::catch_panic(|| decoder::worker::decode_preview(&preview_path, &preview_options)) {
+            Ok(Some((frame, full_size))) => {
+                let _ = preview_tx.send(DecoderMessage::Preview {
+                    rgba: frame.rgba_data,
+                    width: frame.width,
+                    height: frame.height,
+                    full_size,
+                });
+            }
+            Ok(None) => {}
+            Err(e) => log::warn!("Failed to decode preview: {}", e),
+        }
+    });
+
+    match decoder::worker::catch_panic(|| decoder::worker::decode_jxl(&path, &options)) {
         Ok(result) => {
             match result {
-                decoder::DecodeResult::SingleFrame { frame, .. } => {
+                decoder::DecodeResult::SingleFrame { frame, metadata } => {
+                    let _ = tx.send(DecoderMessage::ImageInfo(metadata));
                     let _ = tx.send(DecoderMessage::ProgressiveUpdate {
                         rgba: frame.rgba_data,
                         width: frame.width,
@@ -954,9 +3242,12 @@ fn decode_file(path: PathBuf, tx: Sender<DecoderMessage>) {
                         completed_passes: 1,
                         is_final: true,
                         elapsed: frame.decode_time,
+                        conversion: frame.conversion_time,
                     });
                 }
-                decoder::DecodeResult::Animation { frames, .. } => {
+                decoder::DecodeResult::Animation { frames, metadata } => {
+                    let loop_count = metadata.loop_count;
+                    let _ = tx.send(DecoderMessage::ImageInfo(metadata));
                     let total = frames.len();
                     for (i, frame) in frames.into_iter().enumerate() {
                         let _ = tx.send(DecoderMessage::AnimationFrame {
@@ -966,14 +3257,31 @@ fn decode_file(path: PathBuf, tx: Sender<DecoderMessage>) {
                             duration_ms: frame.duration_ms,
                             frame_index: i,
                             total_frames: total,
+                            loop_count,
                         });
                     }
                 }
             }
             let _ = tx.send(DecoderMessage::Complete);
         }
+        Err(e) if e.is::<decoder::worker::DecoderPanic>() => {
+            let _ = tx.send(DecoderMessage::Crashed(e.to_string()));
+        }
         Err(e) => {
             let _ = tx.send(DecoderMessage::Error(e.to_string()));
         }
     }
 }
+
+/// Fetch a URL into a temporary file named after it
+fn download(url: &str) -> anyhow::Result<PathBuf> {
+    let client = reqwest::blocking::Client::builder()
+        .timeout(clipboard::DOWNLOAD_TIMEOUT)
+        .build()?;
+    let response = client.get(url).send()?.error_for_status()?;
+    if response.content_length().is_some_and(|len| len > clipboard::MAX_DATA_BYTES) {
+        anyhow::bail!("Larger than {} MB", clipboard::MAX_DATA_BYTES / (1024 * 1024));
+    }
+    let bytes = clipboard::read_limited(response, clipboard::MAX_DATA_BYTES)?;
+    clipboard::save_temp(&clipboard::url_file_name(url), &bytes)
+}
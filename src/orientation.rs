@@ -0,0 +1,179 @@
+//! Rotating and flipping the displayed image
+//!
+//! Orientations follow the EXIF convention: a horizontal flip applied first,
+//! then a number of clockwise quarter turns. The eight combinations cover
+//! every EXIF orientation value.
+
+use eframe::egui::{Pos2, Vec2};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Orientation {
+    /// Clockwise quarter turns, 0-3
+    quarter_turns: u8,
+    /// Mirrored left to right before rotating
+    flipped: bool,
+}
+
+impl Orientation {
+    pub const IDENTITY: Self = Self { quarter_turns: 0, flipped: false };
+
+    /// Orientation for an EXIF orientation value (1-8)
+    pub fn from_exif(value: u8) -> Option<Self> {
+        let (quarter_turns, flipped) = match value {
+            1 => (0, false),
+            2 => (0, true),
+            3 => (2, false),
+            4 => (2, true),
+            5 => (3, true),
+            6 => (1, false),
+            7 => (1, true),
+            8 => (3, false),
+            _ => return None,
+        };
+        Some(Self { quarter_turns, flipped })
+    }
+
+    /// EXIF orientation value (1-8), the inverse of [`Self::from_exif`]
+    pub fn to_exif(self) -> u8 {
+        match (self.quarter_turns, self.flipped) {
+            (0, false) => 1,
+            (0, true) => 2,
+            (2, false) => 3,
+            (2, true) => 4,
+            (3, true) => 5,
+            (1, false) => 6,
+            (1, true) => 7,
+            _ => 8,
+        }
+    }
+
+    pub fn is_identity(self) -> bool {
+        self == Self::IDENTITY
+    }
+
+    /// Rotate a further `quarter_turns` clockwise
+    pub fn rotated(self, quarter_turns: u8) -> Self {
+        Self {
+            quarter_turns: (self.quarter_turns + quarter_turns) % 4,
+            ..self
+        }
+    }
+
+    /// Mirror the displayed image left to right
+    pub fn flipped_horizontally(self) -> Self {
+        // Mirroring after a rotation equals mirroring first and rotating the other way
+        Self {
+            quarter_turns: (4 - self.quarter_turns) % 4,
+            flipped: !self.flipped,
+        }
+    }
+
+    /// Mirror the displayed image top to bottom
+    pub fn flipped_vertically(self) -> Self {
+        self.flipped_horizontally().rotated(2)
+    }
+
+    /// Width and height trade places
+    pub fn swaps_axes(self) -> bool {
+        self.quarter_turns % 2 == 1
+    }
+
+    /// Displayed size of an image of `size`
+    pub fn apply_to_size(self, size: Vec2) -> Vec2 {
+        if self.swaps_axes() {
+            Vec2::new(size.y, size.x)
+        } else {
+            size
+        }
+    }
+
+    /// Where a point of the stored image lands, both in 0..1 coordinates
+    pub fn apply(self, point: Pos2) -> Pos2 {
+        let mut p = if self.flipped { Pos2::new(1.0 - point.x, point.y) } else { point };
+        for _ in 0..self.quarter_turns {
+            p = Pos2::new(1.0 - p.y, p.x);
+        }
+        p
+    }
+
+    /// Inverse of [`Self::apply`]: the stored image point shown at a displayed point
+    pub fn unapply(self, point: Pos2) -> Pos2 {
+        let mut p = point;
+        for _ in 0..self.quarter_turns {
+            p = Pos2::new(p.y, 1.0 - p.x);
+        }
+        if self.flipped {
+            p.x = 1.0 - p.x;
+        }
+        p
+    }
+
+    pub fn label(self) -> &'static str {
+        match (self.quarter_turns, self.flipped) {
+            (0, false) => "Normal",
+            (0, true) => "Flipped horizontally",
+            (2, false) => "Rotated 180°",
+            (2, true) => "Flipped vertically",
+            (3, true) => "Transposed",
+            (1, false) => "Rotated 90° CW",
+            (1, true) => "Anti-transposed",
+            _ => "Rotated 90° CCW",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Where the stored top-left and top-right corners end up
+    fn corners(orientation: Orientation) -> (Pos2, Pos2) {
+        (orientation.apply(Pos2::new(0.0, 0.0)), orientation.apply(Pos2::new(1.0, 0.0)))
+    }
+
+    #[test]
+    fn test_exif_values() {
+        let p = Pos2::new;
+        let expected = [
+            (1, p(0.0, 0.0), p(1.0, 0.0)),
+            (2, p(1.0, 0.0), p(0.0, 0.0)),
+            (3, p(1.0, 1.0), p(0.0, 1.0)),
+            (4, p(0.0, 1.0), p(1.0, 1.0)),
+            (5, p(0.0, 0.0), p(0.0, 1.0)),
+            (6, p(1.0, 0.0), p(1.0, 1.0)),
+            (7, p(1.0, 1.0), p(1.0, 0.0)),
+            (8, p(0.0, 1.0), p(0.0, 0.0)),
+        ];
+        for (value, top_left, top_right) in expected {
+            let orientation = Orientation::from_exif(value).unwrap();
+            assert_eq!(corners(orientation), (top_left, top_right), "EXIF {}", value);
+        }
+        for value in 1..=8 {
+            let orientation = Orientation::from_exif(value).unwrap();
+            let p = Pos2::new(0.25, 0.75);
+            assert_eq!(orientation.unapply(orientation.apply(p)), p, "EXIF {}", value);
+            assert_eq!(orientation.to_exif(), value);
+        }
+        assert_eq!(Orientation::from_exif(0), None);
+        assert_eq!(Orientation::from_exif(9), None);
+    }
+
+    #[test]
+    fn test_compose() {
+        let o = Orientation::IDENTITY;
+        assert_eq!(o.rotated(1), Orientation::from_exif(6).unwrap());
+        assert_eq!(o.rotated(1).rotated(3), o);
+        assert_eq!(o.flipped_horizontally(), Orientation::from_exif(2).unwrap());
+        assert_eq!(o.flipped_vertically(), Orientation::from_exif(4).unwrap());
+        assert_eq!(o.flipped_vertically().flipped_vertically(), o);
+
+        // Flipping the rotated view mirrors what is on screen, not the stored image
+        let rotated = o.rotated(1);
+        let flipped = rotated.flipped_horizontally();
+        let (top_left, _) = corners(rotated);
+        let (flipped_top_left, _) = corners(flipped);
+        assert_eq!(flipped_top_left, Pos2::new(1.0 - top_left.x, top_left.y));
+        assert!(flipped.swaps_axes());
+        assert_eq!(flipped.apply_to_size(Vec2::new(400.0, 300.0)), Vec2::new(300.0, 400.0));
+    }
+}
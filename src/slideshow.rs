@@ -0,0 +1,121 @@
+//! Stepping through images automatically
+//!
+//! A slideshow cycles through the open tabs, or with a single tab open,
+//! through the JXL files in the same folder. Animations play to the end of
+//! the loop they are in before the next image is shown.
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+pub struct Slideshow {
+    interval: Duration,
+    /// JXL files in the folder being shown; empty when cycling through tabs
+    files: Vec<PathBuf>,
+    shown_at: Instant,
+    /// Loops the animation on screen had completed when the interval ran out
+    loops_at_deadline: Option<u32>,
+}
+
+impl Slideshow {
+    pub fn new(interval: Duration, files: Vec<PathBuf>) -> Self {
+        Self {
+            interval,
+            files,
+            shown_at: Instant::now(),
+            loops_at_deadline: None,
+        }
+    }
+
+    pub fn is_folder(&self) -> bool {
+        !self.files.is_empty()
+    }
+
+    /// Time until the next image is due, ignoring animations
+    pub fn remaining(&self, now: Instant) -> Duration {
+        (self.shown_at + self.interval).saturating_duration_since(now)
+    }
+
+    /// The current image has been shown long enough
+    ///
+    /// `animation` is the loops completed so far and whether it is still
+    /// playing; a playing animation is only left once it starts a new loop.
+    pub fn is_due(&mut self, now: Instant, animation: Option<(u32, bool)>) -> bool {
+        if now < self.shown_at + self.interval {
+            return false;
+        }
+        match animation {
+            Some((loops, true)) => loops > *self.loops_at_deadline.get_or_insert(loops),
+            _ => true,
+        }
+    }
+
+    /// Start timing the next image
+    pub fn shown(&mut self, now: Instant) {
+        self.shown_at = now;
+        self.loops_at_deadline = None;
+    }
+
+    /// Folder file after `current`, wrapping around
+    pub fn next_file(&self, current: &Path) -> Option<PathBuf> {
+        let next = match self.files.iter().position(|f| f.file_name() == current.file_name()) {
+            Some(i) => (i + 1) % self.files.len(),
+            None => 0,
+        };
+        self.files.get(next).cloned()
+    }
+}
+
+/// JXL files in the folder containing `path`, sorted by name
+pub fn folder_files(path: &Path) -> Vec<PathBuf> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut files: Vec<PathBuf> = entries
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| p.is_file() && p.extension().is_some_and(|e| e.eq_ignore_ascii_case("jxl")))
+        .collect();
+    files.sort();
+    files
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_due() {
+        let start = Instant::now();
+        let mut slideshow = Slideshow::new(Duration::from_secs(5), Vec::new());
+        slideshow.shown(start);
+        let later = start + Duration::from_secs(6);
+
+        assert!(!slideshow.is_due(start + Duration::from_secs(4), None));
+        assert!(slideshow.is_due(later, None));
+        assert_eq!(slideshow.remaining(start + Duration::from_secs(2)), Duration::from_secs(3));
+
+        // A playing animation finishes the loop it is in
+        assert!(!slideshow.is_due(later, Some((3, true))));
+        assert!(!slideshow.is_due(later + Duration::from_secs(1), Some((3, true))));
+        assert!(slideshow.is_due(later + Duration::from_secs(2), Some((4, true))));
+
+        // Paused or finished animations do not hold the slideshow
+        slideshow.shown(start);
+        assert!(slideshow.is_due(later, Some((0, false))));
+    }
+
+    #[test]
+    fn test_next_file() {
+        let files: Vec<PathBuf> = ["a.jxl", "b.jxl", "c.jxl"].iter().map(PathBuf::from).collect();
+        let slideshow = Slideshow::new(Duration::from_secs(1), files);
+        assert!(slideshow.is_folder());
+        assert_eq!(slideshow.next_file(Path::new("./a.jxl")), Some(PathBuf::from("b.jxl")));
+        assert_eq!(slideshow.next_file(Path::new("c.jxl")), Some(PathBuf::from("a.jxl")));
+        // A file that has since disappeared restarts from the beginning
+        assert_eq!(slideshow.next_file(Path::new("x.jxl")), Some(PathBuf::from("a.jxl")));
+        assert!(!Slideshow::new(Duration::from_secs(1), Vec::new()).is_folder());
+    }
+}
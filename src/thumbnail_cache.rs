@@ -0,0 +1,207 @@
+//! Thumbnails shared with file managers, following the freedesktop.org
+//! thumbnail managing standard
+//!
+//! Thumbnails live in `$XDG_CACHE_HOME/thumbnails/<size>/<md5 of URI>.png` and
+//! carry the source's URI and modification time as PNG text chunks; a
+//! thumbnail whose `Thumb::MTime` no longer matches the file is stale.
+
+use crate::crop;
+use anyhow::{bail, Result};
+use jxl_ui::decoder::{self, worker::DecodeOptions};
+use std::io::{Cursor, Write};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// A decoded thumbnail with straight alpha
+pub struct Thumbnail {
+    pub width: u32,
+    pub height: u32,
+    pub rgba: Vec<u8>,
+}
+
+/// Decode `path` scaled to at most `size` pixels on its longer side
+pub fn generate(path: &Path, size: u32, options: &DecodeOptions) -> Result<Thumbnail> {
+    let mut frame = decoder::worker::decode_thumbnail(path, size, options)?;
+    crop::unpremultiply(&mut frame.rgba_data);
+    Ok(Thumbnail {
+        width: frame.width,
+        height: frame.height,
+        rgba: frame.rgba_data,
+    })
+}
+
+/// Read the cached thumbnail of `path`, or generate and cache a new one
+pub fn load_or_generate(path: &Path, size: u32, options: &DecodeOptions) -> Result<Thumbnail> {
+    let Some((uri, mtime)) = file_uri(path).zip(mtime(path)) else {
+        return generate(path, size, options);
+    };
+    let cache_path = cache_path(&uri, size);
+    if let Some(thumbnail) = cache_path.as_deref().and_then(|c| read(c, &uri, mtime)) {
+        return Ok(thumbnail);
+    }
+
+    let thumbnail = generate(path, size, options)?;
+    if let Some(cache_path) = cache_path {
+        let saved = cache_path.parent().map_or(Ok(()), create_private_dir)
+            .map_err(anyhow::Error::from)
+            .and_then(|_| save(&cache_path, &thumbnail, path));
+        if let Err(e) = saved {
+            log::warn!("Failed to cache thumbnail {:?}: {}", cache_path, e);
+        }
+    }
+    Ok(thumbnail)
+}
+
+/// Write `thumbnail` of `source` as a PNG with the standard's metadata
+///
+/// The file appears atomically, so other programs never read it half-written.
+pub fn save(out: &Path, thumbnail: &Thumbnail, source: &Path) -> Result<()> {
+    let (Some(uri), Some(mtime)) = (file_uri(source), mtime(source)) else {
+        bail!("Cannot read {:?}", source);
+    };
+    let file_size = std::fs::metadata(source)?.len();
+
+    let mut png = Vec::new();
+    let mut encoder = png::Encoder::new(&mut png, thumbnail.width, thumbnail.height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.add_text_chunk("Thumb::URI".to_string(), uri)?;
+    encoder.add_text_chunk("Thumb::MTime".to_string(), mtime.to_string())?;
+    encoder.add_text_chunk("Thumb::Size".to_string(), file_size.to_string())?;
+    encoder.add_text_chunk("Thumb::Mimetype".to_string(), "image/jxl".to_string())?;
+    encoder.add_text_chunk("Software".to_string(), format!("jxl-ui {}", env!("CARGO_PKG_VERSION")))?;
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&thumbnail.rgba)?;
+    writer.finish()?;
+
+    let temp = out.with_extension(format!("png.{}.tmp", std::process::id()));
+    let result = write_private(&temp, &png).and_then(|_| std::fs::rename(&temp, out));
+    if result.is_err() {
+        std::fs::remove_file(&temp).ok();
+    }
+    Ok(result?)
+}
+
+/// Decode a cached thumbnail if it was made from the current version of `uri`
+fn read(path: &Path, uri: &str, mtime: u64) -> Option<Thumbnail> {
+    let bytes = std::fs::read(path).ok()?;
+    let reader = png::Decoder::new(Cursor::new(&bytes)).read_info().ok()?;
+    let text = |key: &str| {
+        reader.info().uncompressed_latin1_text.iter()
+            .find(|chunk| chunk.keyword == key)
+            .map(|chunk| chunk.text.clone())
+    };
+    if text("Thumb::URI").as_deref() != Some(uri) || text("Thumb::MTime") != Some(mtime.to_string()) {
+        return None;
+    }
+
+    let image = image::load_from_memory_with_format(&bytes, image::ImageFormat::Png)
+        .map_err(|e| log::warn!("Ignoring unreadable thumbnail {:?}: {}", path, e))
+        .ok()?
+        .to_rgba8();
+    Some(Thumbnail {
+        width: image.width(),
+        height: image.height(),
+        rgba: image.into_raw(),
+    })
+}
+
+/// Cache location of the thumbnail for `uri`, in the directory for `size`
+pub fn cache_path(uri: &str, size: u32) -> Option<PathBuf> {
+    let name = format!("{:x}.png", md5::compute(uri));
+    Some(dirs::cache_dir()?.join("thumbnails").join(size_dir(size)).join(name))
+}
+
+/// Directory name of the standard size class holding thumbnails of `size` pixels
+fn size_dir(size: u32) -> &'static str {
+    match size {
+        0..=128 => "normal",
+        129..=256 => "large",
+        257..=512 => "x-large",
+        _ => "xx-large",
+    }
+}
+
+/// Canonical `file://` URI of a file, as used to name its thumbnail
+pub fn file_uri(path: &Path) -> Option<String> {
+    let absolute = std::fs::canonicalize(path).ok()?;
+    Some(format!("file://{}", encode_path(&absolute.to_string_lossy())))
+}
+
+/// Percent-encode a path the way GLib builds file URIs
+fn encode_path(path: &str) -> String {
+    let mut out = String::with_capacity(path.len());
+    for byte in path.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~!$&'()*+,;=:@/".contains(&byte) {
+            out.push(byte as char);
+        } else {
+            out.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    out
+}
+
+/// Modification time in whole seconds, as stored in `Thumb::MTime`
+fn mtime(path: &Path) -> Option<u64> {
+    let modified = std::fs::metadata(path).ok()?.modified().ok()?;
+    Some(modified.duration_since(UNIX_EPOCH).ok()?.as_secs())
+}
+
+/// Thumbnail directories are only readable by their owner
+fn create_private_dir(dir: &Path) -> std::io::Result<()> {
+    let mut builder = std::fs::DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)]
+    std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+    builder.create(dir)
+}
+
+fn write_private(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(path)?.write_all(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_uri_and_cache_path() {
+        assert_eq!(encode_path("/home/me/My Photos/ü#1.jxl"), "/home/me/My%20Photos/%C3%BC%231.jxl");
+        assert_eq!(size_dir(128), "normal");
+        assert_eq!(size_dir(256), "large");
+        assert_eq!(size_dir(1024), "xx-large");
+
+        // Example from the thumbnail managing standard
+        if let Some(path) = cache_path("file:///home/jens/photos/me.png", 128) {
+            assert!(path.ends_with("thumbnails/normal/c6ee772d9e49320e97ec29a7eb5b1697.png"));
+        }
+        assert_eq!(file_uri(Path::new("/no/such/file.jxl")), None);
+    }
+
+    #[test]
+    fn test_save_and_read() {
+        let dir = std::env::temp_dir().join(format!("jxl-ui-thumbnail-cache-{}", std::process::id()));
+        create_private_dir(&dir).unwrap();
+        let source = dir.join("image.jxl");
+        std::fs::write(&source, b"not really an image").unwrap();
+        let thumbnail = Thumbnail { width: 2, height: 1, rgba: vec![255, 0, 0, 255, 0, 0, 255, 128] };
+        let out = dir.join("thumbnail.png");
+        save(&out, &thumbnail, &source).unwrap();
+
+        let uri = file_uri(&source).unwrap();
+        let mtime = mtime(&source).unwrap();
+        let read_back = read(&out, &uri, mtime).unwrap();
+        assert_eq!((read_back.width, read_back.height), (2, 1));
+        assert_eq!(read_back.rgba, thumbnail.rgba);
+
+        // Thumbnails of an older version of the file, or of another file, are not used
+        assert!(read(&out, &uri, mtime + 1).is_none());
+        assert!(read(&out, "file:///elsewhere.jxl", mtime).is_none());
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,131 @@
+//! Small previews of open tabs and folder contents
+//!
+//! Thumbnails are generated by a pool of background threads and kept in the
+//! shared thumbnail cache, so reopening a folder does not decode every file
+//! again and file managers can show them too.
+
+use crate::thumbnail_cache;
+use anyhow::Result;
+use eframe::egui;
+use jxl_ui::decoder::{self, worker::DecodeOptions};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// Longer side of a generated thumbnail, in pixels; the standard's "normal" size
+pub const THUMBNAIL_SIZE: u32 = 128;
+
+/// Most thumbnails generated at once
+const MAX_WORKERS: usize = 4;
+
+enum Entry {
+    Pending,
+    Ready(egui::TextureHandle),
+    Failed(String),
+}
+
+pub struct Thumbnails {
+    entries: HashMap<PathBuf, Entry>,
+    jobs: Sender<(PathBuf, DecodeOptions)>,
+    /// Pixel limit and threads for new jobs, following the settings
+    options: DecodeOptions,
+    results: Receiver<(PathBuf, Result<egui::ColorImage, String>)>,
+    /// Last folder listed, with its JXL files
+    folder: Option<(PathBuf, Vec<PathBuf>)>,
+}
+
+impl Thumbnails {
+    pub fn new(ctx: egui::Context) -> Self {
+        let (jobs, job_rx) = channel::<(PathBuf, DecodeOptions)>();
+        let (result_tx, results) = channel();
+        let job_rx = Arc::new(Mutex::new(job_rx));
+
+        let workers = thread::available_parallelism().map_or(1, |n| n.get() / 2).clamp(1, MAX_WORKERS);
+        for _ in 0..workers {
+            let job_rx = job_rx.clone();
+            let result_tx = result_tx.clone();
+            let ctx = ctx.clone();
+            // The lock is only held while waiting for a job, not while decoding
+            thread::spawn(move || {
+                while let Some((path, options)) = job_rx.lock().ok().and_then(|rx| rx.recv().ok()) {
+                    let result = decoder::worker::catch_panic(|| load_or_generate(&path, &options))
+                        .map_err(|e| e.to_string());
+                    if result_tx.send((path, result)).is_err() {
+                        break;
+                    }
+                    ctx.request_repaint();
+                }
+            });
+        }
+
+        Self {
+            entries: HashMap::new(),
+            jobs,
+            options: DecodeOptions::default(),
+            results,
+            folder: None,
+        }
+    }
+
+    /// Decode later thumbnails with `options`
+    pub fn set_options(&mut self, options: DecodeOptions) {
+        self.options = options;
+    }
+
+    /// Upload finished thumbnails
+    pub fn update(&mut self, ctx: &egui::Context) {
+        while let Ok((path, result)) = self.results.try_recv() {
+            let entry = match result {
+                Ok(image) => {
+                    let name = format!("thumbnail-{}", path.display());
+                    Entry::Ready(ctx.load_texture(name, image, egui::TextureOptions::LINEAR))
+                }
+                Err(e) => {
+                    log::warn!("Failed to create thumbnail for {:?}: {}", path, e);
+                    Entry::Failed(e)
+                }
+            };
+            self.entries.insert(path, entry);
+        }
+    }
+
+    /// Thumbnail of `path`, queueing it if it has not been requested yet
+    ///
+    /// `Err` holds the reason a thumbnail could not be made.
+    pub fn get(&mut self, path: &Path) -> Option<Result<&egui::TextureHandle, &str>> {
+        let entry = self.entries.entry(path.to_path_buf()).or_insert_with(|| {
+            let _ = self.jobs.send((path.to_path_buf(), self.options.clone()));
+            Entry::Pending
+        });
+        match entry {
+            Entry::Pending => None,
+            Entry::Ready(texture) => Some(Ok(texture)),
+            Entry::Failed(e) => Some(Err(e)),
+        }
+    }
+
+    /// Forget the thumbnail of a file that changed on disk
+    pub fn invalidate(&mut self, path: &Path) {
+        if !matches!(self.entries.get(path), Some(Entry::Pending)) {
+            self.entries.remove(path);
+        }
+    }
+
+    /// JXL files in the folder of `path`, listed again only when the folder changes
+    pub fn folder_files(&mut self, path: &Path) -> &[PathBuf] {
+        let dir = path.parent().unwrap_or(Path::new("")).to_path_buf();
+        if !matches!(&self.folder, Some((d, _)) if *d == dir) {
+            self.folder = Some((dir, crate::slideshow::folder_files(path)));
+        }
+        self.folder.as_ref().map_or(&[], |(_, files)| files)
+    }
+}
+
+/// Thumbnail of `path` as a texture image
+fn load_or_generate(path: &Path, options: &DecodeOptions) -> Result<egui::ColorImage> {
+    let thumbnail = thumbnail_cache::load_or_generate(path, THUMBNAIL_SIZE, options)?;
+    let size = [thumbnail.width as usize, thumbnail.height as usize];
+    Ok(egui::ColorImage::from_rgba_unmultiplied(size, &thumbnail.rgba))
+}
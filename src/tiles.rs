@@ -0,0 +1,362 @@
+//! Tiled textures for images larger than the GPU texture size limit
+//!
+//! Pixels stay on the CPU and are uploaded as a grid of tiles when they come
+//! into view. Zoomed-out views draw from half-resolution levels, which are
+//! built on a background thread, and tiles that leave the view are released, so
+//! GPU memory is bounded by what is on screen.
+
+use crate::orientation::Orientation;
+use eframe::egui::{self, epaint, Color32, Pos2, Rect, Rounding, Vec2};
+use std::sync::mpsc::{channel, Receiver, TryRecvError};
+use std::sync::Arc;
+use std::thread;
+
+/// Largest tile edge, well within every backend's texture limit
+pub const MAX_TILE_SIZE: usize = 2048;
+
+/// Fill shown while the level for the current zoom is still being built
+const PLACEHOLDER: Color32 = Color32::from_gray(28);
+
+/// Pixels and size of a level built in the background
+type BuiltLevel = (Arc<Vec<u8>>, usize, usize);
+
+/// One resolution level of the image, downscaled by `2^index`
+struct Level {
+    width: usize,
+    height: usize,
+    rgba: Arc<Vec<u8>>,
+    columns: usize,
+    /// Uploaded tiles, row-major; `None` when not resident on the GPU
+    tiles: Vec<Option<egui::TextureHandle>>,
+}
+
+impl Level {
+    fn new(width: usize, height: usize, rgba: Arc<Vec<u8>>, tile_size: usize) -> Self {
+        let columns = width.div_ceil(tile_size).max(1);
+        let rows = height.div_ceil(tile_size).max(1);
+        Self {
+            width,
+            height,
+            rgba,
+            columns,
+            tiles: vec![None; columns * rows],
+        }
+    }
+
+    /// Pixel bounds of a tile within this level
+    fn tile_bounds(&self, index: usize, tile_size: usize) -> (usize, usize, usize, usize) {
+        let x0 = (index % self.columns) * tile_size;
+        let y0 = (index / self.columns) * tile_size;
+        (x0, y0, (x0 + tile_size).min(self.width), (y0 + tile_size).min(self.height))
+    }
+
+    /// Copy the pixels of one tile out of the level
+    fn tile_image(&self, index: usize, tile_size: usize) -> egui::ColorImage {
+        let (x0, y0, x1, y1) = self.tile_bounds(index, tile_size);
+        let row_bytes = (x1 - x0) * 4;
+        let mut pixels = Vec::with_capacity(row_bytes * (y1 - y0));
+        for y in y0..y1 {
+            let start = (y * self.width + x0) * 4;
+            pixels.extend_from_slice(&self.rgba[start..start + row_bytes]);
+        }
+        egui::ColorImage::from_rgba_unmultiplied([x1 - x0, y1 - y0], &pixels)
+    }
+
+    fn release(&mut self) {
+        self.tiles.iter_mut().for_each(|t| *t = None);
+    }
+}
+
+/// An RGBA8 image drawn as a grid of textures with downscaled levels
+pub struct TiledImage {
+    name: String,
+    width: usize,
+    height: usize,
+    tile_size: usize,
+    /// Size the image is laid out at; larger than the pixels for previews
+    display_size: Vec2,
+    /// Level `i` is downscaled by `2^i`; built in the background on first use
+    levels: Vec<Level>,
+    /// Levels still being built, arriving in order
+    pending: Option<Receiver<BuiltLevel>>,
+}
+
+impl TiledImage {
+    /// Wrap decoded pixels; nothing is uploaded until the image is painted
+    pub fn new(name: impl Into<String>, rgba: Arc<Vec<u8>>, width: usize, height: usize, max_texture_side: usize) -> Self {
+        let tile_size = MAX_TILE_SIZE.min(max_texture_side).max(1);
+        Self {
+            name: name.into(),
+            width,
+            height,
+            tile_size,
+            display_size: Vec2::new(width as f32, height as f32),
+            levels: vec![Level::new(width, height, rgba, tile_size)],
+            pending: None,
+        }
+    }
+
+    /// Lay the image out at another size, e.g. a preview standing in for the full image
+    pub fn with_display_size(mut self, width: usize, height: usize) -> Self {
+        self.display_size = Vec2::new(width as f32, height as f32);
+        self
+    }
+
+    pub fn size_vec2(&self) -> Vec2 {
+        self.display_size
+    }
+
+    /// The pixels are a reduced-resolution stand-in for the displayed size
+    pub fn is_preview(&self) -> bool {
+        self.display_size != Vec2::new(self.width as f32, self.height as f32)
+    }
+
+    /// Number of levels needed until the whole image fits in one tile
+    fn level_count(&self) -> usize {
+        let mut count = 1;
+        let mut side = self.width.max(self.height);
+        while side > self.tile_size {
+            side = side.div_ceil(2);
+            count += 1;
+        }
+        count
+    }
+
+    /// Level to draw from when showing the image at `scale` physical pixels per image pixel
+    pub fn level_for_scale(&self, scale: f32) -> usize {
+        if scale <= 0.0 || scale >= 1.0 {
+            return 0;
+        }
+        let level = (1.0 / scale).log2().floor() as usize;
+        level.min(self.level_count() - 1)
+    }
+
+    /// Whether level `index` is ready, starting to build the missing levels if not
+    ///
+    /// All remaining levels are built in one go on a background thread, so
+    /// zooming out further does not have to wait again.
+    fn ensure_level(&mut self, index: usize, ctx: &egui::Context) -> bool {
+        self.receive_levels();
+        if index < self.levels.len() {
+            return true;
+        }
+        if self.pending.is_none() {
+            let prev = self.levels.last().unwrap();
+            let (mut rgba, mut width, mut height) = (prev.rgba.clone(), prev.width, prev.height);
+            let missing = self.level_count() - self.levels.len();
+            let (tx, rx) = channel();
+            let ctx = ctx.clone();
+            thread::spawn(move || {
+                for _ in 0..missing {
+                    let (next, next_width, next_height) = downscale_half(&rgba, width, height);
+                    (rgba, width, height) = (Arc::new(next), next_width, next_height);
+                    // The image was dropped
+                    if tx.send((rgba.clone(), width, height)).is_err() {
+                        return;
+                    }
+                    ctx.request_repaint();
+                }
+            });
+            self.pending = Some(rx);
+        }
+        false
+    }
+
+    /// Add the levels finished by the background thread
+    fn receive_levels(&mut self) {
+        while let Some(rx) = &self.pending {
+            match rx.try_recv() {
+                Ok((rgba, width, height)) => self.levels.push(Level::new(width, height, rgba, self.tile_size)),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => self.pending = None,
+            }
+        }
+    }
+
+    /// The full-resolution image does not fit in a single tile
+    pub fn is_tiled(&self) -> bool {
+        self.levels[0].tiles.len() > 1
+    }
+
+    /// Drop every uploaded tile; they are uploaded again when next painted
+    pub fn release(&mut self) {
+        self.levels.iter_mut().for_each(Level::release);
+    }
+
+    /// Bytes of texture memory currently uploaded
+    pub fn resident_bytes(&self) -> usize {
+        self.levels
+            .iter()
+            .flat_map(|l| l.tiles.iter().flatten())
+            .map(|t| t.size()[0] * t.size()[1] * 4)
+            .sum()
+    }
+
+    /// Bytes of decoded and downscaled pixels held on the CPU
+    pub fn cpu_bytes(&self) -> usize {
+        self.levels.iter().map(|l| l.rgba.len()).sum()
+    }
+
+    /// Draw the image into `rect` as seen through `orientation`, uploading
+    /// visible tiles and releasing the rest
+    pub fn paint(&mut self, ui: &egui::Ui, rect: Rect, orientation: Orientation) {
+        let pixels_per_point = ui.ctx().pixels_per_point();
+        let width_on_screen = if orientation.swaps_axes() { rect.height() } else { rect.width() };
+        let level_index = self.level_for_scale(width_on_screen * pixels_per_point / self.width as f32);
+        let ready = self.ensure_level(level_index, ui.ctx());
+
+        for (i, level) in self.levels.iter_mut().enumerate() {
+            if i != level_index {
+                level.release();
+            }
+        }
+        if !ready {
+            ui.painter().rect_filled(rect, Rounding::same(4.0), PLACEHOLDER);
+            return;
+        }
+
+        let tile_size = self.tile_size;
+        let level = &mut self.levels[level_index];
+        let clip = ui.clip_rect().intersect(rect);
+        let level_size = Vec2::new(level.width as f32, level.height as f32);
+        let to_screen = |x: usize, y: usize| {
+            let p = orientation.apply(Pos2::new(x as f32 / level_size.x, y as f32 / level_size.y));
+            rect.min + p.to_vec2() * rect.size()
+        };
+
+        for index in 0..level.tiles.len() {
+            let (x0, y0, x1, y1) = level.tile_bounds(index, tile_size);
+            // Corners in the order of the texture's UV corners
+            let corners = [to_screen(x0, y0), to_screen(x1, y0), to_screen(x1, y1), to_screen(x0, y1)];
+            let tile_rect = Rect::from_points(&corners);
+            if !clip.intersects(tile_rect) {
+                level.tiles[index] = None;
+                continue;
+            }
+
+            let texture = match &level.tiles[index] {
+                Some(texture) => texture.clone(),
+                None => {
+                    let texture = ui.ctx().load_texture(
+                        format!("{}-l{}-t{}", self.name, level_index, index),
+                        level.tile_image(index, tile_size),
+                        egui::TextureOptions::LINEAR,
+                    );
+                    level.tiles[index] = Some(texture.clone());
+                    texture
+                }
+            };
+
+            if level.tiles.len() == 1 && orientation.is_identity() {
+                egui::Image::new((texture.id(), rect.size()))
+                    .rounding(Rounding::same(4.0))
+                    .paint_at(ui, rect);
+            } else {
+                let uvs = [Pos2::new(0.0, 0.0), Pos2::new(1.0, 0.0), Pos2::new(1.0, 1.0), Pos2::new(0.0, 1.0)];
+                let mut mesh = epaint::Mesh::with_texture(texture.id());
+                for (pos, uv) in corners.into_iter().zip(uvs) {
+                    mesh.vertices.push(epaint::Vertex { pos, uv, color: Color32::WHITE });
+                }
+                mesh.add_triangle(0, 1, 2);
+                mesh.add_triangle(0, 2, 3);
+                ui.painter().add(egui::Shape::mesh(mesh));
+            }
+        }
+    }
+}
+
+/// Halve an RGBA8 image with a 2x2 box filter, rounding odd edges up
+pub fn downscale_half(rgba: &[u8], width: usize, height: usize) -> (Vec<u8>, usize, usize) {
+    let out_width = width.div_ceil(2).max(1);
+    let out_height = height.div_ceil(2).max(1);
+    let mut out = vec![0u8; out_width * out_height * 4];
+
+    for y in 0..out_height {
+        let (sy0, sy1) = (y * 2, (y * 2 + 1).min(height - 1));
+        for x in 0..out_width {
+            let (sx0, sx1) = (x * 2, (x * 2 + 1).min(width - 1));
+            for c in 0..4 {
+                let sum: u32 = [(sx0, sy0), (sx1, sy0), (sx0, sy1), (sx1, sy1)]
+                    .iter()
+                    .map(|&(sx, sy)| rgba[(sy * width + sx) * 4 + c] as u32)
+                    .sum();
+                out[(y * out_width + x) * 4 + c] = ((sum + 2) / 4) as u8;
+            }
+        }
+    }
+
+    (out, out_width, out_height)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn solid(width: usize, height: usize) -> TiledImage {
+        TiledImage::new("test", Arc::new(vec![0; width * height * 4]), width, height, 16384)
+    }
+
+    #[test]
+    fn test_downscale_half() {
+        // 3x1 image: the odd last column is averaged with itself
+        let rgba = [0, 0, 0, 255, 100, 100, 100, 255, 40, 40, 40, 255];
+        let (out, w, h) = downscale_half(&rgba, 3, 1);
+        assert_eq!((w, h), (2, 1));
+        assert_eq!(out, [50, 50, 50, 255, 40, 40, 40, 255]);
+    }
+
+    #[test]
+    fn test_tile_grid() {
+        let image = solid(5000, 2100);
+        let level = &image.levels[0];
+        assert_eq!((level.columns, level.tiles.len()), (3, 6));
+        assert_eq!(level.tile_bounds(2, image.tile_size), (4096, 0, 5000, 2048));
+        assert_eq!(level.tile_bounds(5, image.tile_size), (4096, 2048, 5000, 2100));
+        assert_eq!(level.tile_image(5, image.tile_size).size, [904, 52]);
+        assert!(image.is_tiled());
+        assert_eq!(image.resident_bytes(), 0);
+    }
+
+    #[test]
+    fn test_level_for_scale() {
+        let image = solid(20000, 10000);
+        assert_eq!(image.level_count(), 5); // 20000 -> 10000 -> 5000 -> 2500 -> 1250
+        assert_eq!(image.level_for_scale(2.0), 0);
+        assert_eq!(image.level_for_scale(0.6), 0);
+        assert_eq!(image.level_for_scale(0.5), 1);
+        assert_eq!(image.level_for_scale(0.2), 2);
+        assert_eq!(image.level_for_scale(0.001), 4);
+
+        // Images that fit in one tile never need a smaller level
+        assert_eq!(solid(800, 600).level_for_scale(0.1), 0);
+    }
+
+    #[test]
+    fn test_background_levels() {
+        let ctx = egui::Context::default();
+        let mut image = solid(5000, 2100);
+        assert!(image.ensure_level(0, &ctx));
+
+        // 5000 -> 2500 -> 1250 pixels across, built together
+        let start = std::time::Instant::now();
+        while !image.ensure_level(2, &ctx) {
+            assert!(start.elapsed() < std::time::Duration::from_secs(10), "levels never arrived");
+            thread::sleep(std::time::Duration::from_millis(1));
+        }
+        assert_eq!(image.levels.len(), 3);
+        assert_eq!((image.levels[2].width, image.levels[2].height), (1250, 525));
+        assert_eq!(image.cpu_bytes(), (5000 * 2100 + 2500 * 1050 + 1250 * 525) * 4);
+    }
+
+    #[test]
+    fn test_preview_display_size() {
+        let image = solid(400, 300);
+        assert!(!image.is_preview());
+
+        let preview = solid(400, 300).with_display_size(16000, 12000);
+        assert!(preview.is_preview());
+        assert_eq!(preview.size_vec2(), Vec2::new(16000.0, 12000.0));
+        // Level choice follows the preview's own pixels, not the display size
+        assert_eq!(preview.level_for_scale(0.5), 0);
+    }
+}
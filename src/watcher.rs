@@ -0,0 +1,176 @@
+//! Reloading open files when they change on disk
+//!
+//! Parent directories are watched rather than the files themselves, so writers
+//! that replace a file with an atomic rename are seen as a change too.
+
+use eframe::egui;
+use notify::event::{EventKind, ModifyKind};
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver};
+use std::time::{Duration, Instant};
+
+/// Quiet period after the last change before a file is reloaded
+pub const DEBOUNCE: Duration = Duration::from_millis(250);
+
+/// Collapses bursts of change events into one reload per file
+#[derive(Default)]
+pub struct Debouncer {
+    pending: HashMap<PathBuf, Instant>,
+}
+
+impl Debouncer {
+    pub fn record(&mut self, path: PathBuf, now: Instant) {
+        self.pending.insert(path, now);
+    }
+
+    /// Files that have been quiet for at least [`DEBOUNCE`]
+    pub fn take_ready(&mut self, now: Instant) -> Vec<PathBuf> {
+        let ready: Vec<PathBuf> = self.pending
+            .iter()
+            .filter(|(_, &last)| now.duration_since(last) >= DEBOUNCE)
+            .map(|(path, _)| path.clone())
+            .collect();
+        for path in &ready {
+            self.pending.remove(path);
+        }
+        ready
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+}
+
+/// Watches the files open in tabs and reports the ones that changed
+pub struct FileWatcher {
+    watcher: RecommendedWatcher,
+    rx: Receiver<PathBuf>,
+    watched_dirs: HashSet<PathBuf>,
+    /// Paths as last passed to [`Self::sync`], before resolving
+    opened: Vec<PathBuf>,
+    /// Resolved path as reported by the watcher -> path as opened
+    files: HashMap<PathBuf, PathBuf>,
+    debouncer: Debouncer,
+}
+
+impl FileWatcher {
+    pub fn new(ctx: egui::Context) -> notify::Result<Self> {
+        let (tx, rx) = channel();
+        let watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+            let Ok(event) = event else {
+                return;
+            };
+            // Reading the file ourselves produces access events; ignore those
+            let is_change = matches!(
+                event.kind,
+                EventKind::Create(_)
+                    | EventKind::Remove(_)
+                    | EventKind::Modify(ModifyKind::Data(_) | ModifyKind::Name(_) | ModifyKind::Any)
+            );
+            if is_change {
+                for path in event.paths {
+                    let _ = tx.send(path);
+                }
+                ctx.request_repaint();
+            }
+        })?;
+
+        Ok(Self {
+            watcher,
+            rx,
+            watched_dirs: HashSet::new(),
+            opened: Vec::new(),
+            files: HashMap::new(),
+            debouncer: Debouncer::default(),
+        })
+    }
+
+    /// Watch exactly the given files, starting and stopping directory watches as needed
+    ///
+    /// Called every frame, so paths are only resolved when the list changes.
+    pub fn sync<'a>(&mut self, paths: impl Iterator<Item = &'a Path>) {
+        let paths: Vec<&Path> = paths.collect();
+        if paths.iter().copied().eq(self.opened.iter().map(PathBuf::as_path)) {
+            return;
+        }
+        self.opened = paths.iter().map(|p| p.to_path_buf()).collect();
+
+        let files: HashMap<PathBuf, PathBuf> = paths.iter()
+            .filter_map(|path| Some((resolve(path)?, path.to_path_buf())))
+            .collect();
+        if files == self.files {
+            return;
+        }
+
+        let dirs: HashSet<PathBuf> = files.keys()
+            .filter_map(|p| p.parent().map(Path::to_path_buf))
+            .collect();
+        for dir in self.watched_dirs.difference(&dirs) {
+            let _ = self.watcher.unwatch(dir);
+        }
+        for dir in dirs.difference(&self.watched_dirs) {
+            if let Err(e) = self.watcher.watch(dir, RecursiveMode::NonRecursive) {
+                log::warn!("Failed to watch {:?}: {}", dir, e);
+            }
+        }
+
+        self.watched_dirs = dirs;
+        self.files = files;
+    }
+
+    /// Files that changed and have settled, as they were passed to [`Self::sync`]
+    pub fn poll(&mut self) -> Vec<PathBuf> {
+        let now = Instant::now();
+        while let Ok(path) = self.rx.try_recv() {
+            if let Some(opened) = self.files.get(&path) {
+                self.debouncer.record(opened.clone(), now);
+            }
+        }
+        self.debouncer.take_ready(now)
+    }
+
+    /// Changes are waiting out the debounce period
+    pub fn has_pending(&self) -> bool {
+        !self.debouncer.is_empty()
+    }
+}
+
+/// Absolute path with the parent directory resolved, which still works while
+/// the file itself is missing in the middle of an atomic rename
+fn resolve(path: &Path) -> Option<PathBuf> {
+    let parent = match path.parent() {
+        Some(p) if !p.as_os_str().is_empty() => p,
+        _ => Path::new("."),
+    };
+    Some(std::fs::canonicalize(parent).ok()?.join(path.file_name()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_debouncer() {
+        let start = Instant::now();
+        let mut debouncer = Debouncer::default();
+        let path = PathBuf::from("out.jxl");
+
+        // A burst of events keeps pushing the reload back
+        debouncer.record(path.clone(), start);
+        debouncer.record(path.clone(), start + Duration::from_millis(200));
+        assert!(debouncer.take_ready(start + Duration::from_millis(300)).is_empty());
+
+        assert_eq!(debouncer.take_ready(start + Duration::from_millis(450)), vec![path]);
+        assert!(debouncer.is_empty());
+    }
+
+    #[test]
+    fn test_resolve() {
+        let dir = std::env::temp_dir();
+        let resolved = resolve(&dir.join("missing.jxl")).unwrap();
+        assert_eq!(resolved, std::fs::canonicalize(&dir).unwrap().join("missing.jxl"));
+        assert!(resolve(Path::new("relative.jxl")).unwrap().is_absolute());
+    }
+}
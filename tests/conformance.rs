@@ -0,0 +1,18 @@
+//! Conformance suite run, enabled by pointing `JXL_CONFORMANCE_DIR` at a
+//! checkout of github.com/libjxl/conformance
+
+use jxl_ui::conformance::{format_matrix, run_suite};
+use std::path::PathBuf;
+
+#[test]
+fn test_conformance_suite() {
+    let Some(dir) = std::env::var_os("JXL_CONFORMANCE_DIR").map(PathBuf::from) else {
+        eprintln!("skipping conformance suite: JXL_CONFORMANCE_DIR is not set");
+        return;
+    };
+
+    let results = run_suite(&dir).unwrap();
+    let matrix = format_matrix(&results);
+    println!("{}", matrix);
+    assert!(results.iter().all(|r| r.outcome.is_pass()), "conformance failures:\n{}", matrix);
+}
@@ -0,0 +1,613 @@
+#!/usr/bin/env python3
+"""Write the JXL test fixtures and their PNG references.
+
+Every fixture is a bare lossless Modular codestream written by the small encoder
+below, so the files can be regenerated with nothing but Python 3. Because the
+files are lossless, the references are the source pixels themselves (with the
+header orientation applied, as a decoder displays them). If libjxl's `djxl` is
+on the PATH, `--check` decodes every fixture with it and compares the result
+against the reference.
+
+The encoder only supports what the fixtures need: one group per frame, no
+transforms, a single-leaf MA tree with the gradient predictor and fixed-length
+prefix codes.
+"""
+
+import argparse
+import os
+import shutil
+import struct
+import subprocess
+import sys
+import tempfile
+import zlib
+
+HERE = os.path.dirname(os.path.abspath(__file__))
+
+# Source images are small and not a multiple of 8, so edges get exercised
+WIDTH, HEIGHT = 45, 30
+
+
+# --- Bit writer and field encodings -----------------------------------------
+
+
+class BitWriter:
+    """Writes bits least significant first, like the JPEG XL bit reader"""
+
+    def __init__(self):
+        self.data = bytearray()
+        self.nbits = 0
+
+    def bits(self, value, n):
+        assert 0 <= value < (1 << n) or n == 0 and value == 0, (value, n)
+        for i in range(n):
+            if self.nbits % 8 == 0:
+                self.data.append(0)
+            if (value >> i) & 1:
+                self.data[-1] |= 1 << (self.nbits % 8)
+            self.nbits += 1
+
+    def bool(self, value):
+        self.bits(1 if value else 0, 1)
+
+    def pad_to_byte(self):
+        while self.nbits % 8:
+            self.bits(0, 1)
+
+    def u32(self, value, dist):
+        """U32 field; `dist` holds four (bits, offset) pairs, bits 0 for a constant"""
+        for selector, (n, offset) in enumerate(dist):
+            if value >= offset and value - offset < (1 << n) and (n > 0 or value == offset):
+                self.bits(selector, 2)
+                self.bits(value - offset, n)
+                return
+        raise ValueError("%d does not fit %r" % (value, dist))
+
+    def u64_zero(self):
+        self.bits(0, 2)
+
+    def enum(self, value):
+        self.u32(value, ENUM)
+
+    def bytes(self):
+        return bytes(self.data)
+
+
+def val(v):
+    return (0, v)
+
+
+ENUM = [val(0), val(1), (4, 2), (6, 18)]
+SIZE = [(9, 1), (13, 1), (18, 1), (30, 1)]
+CROP = [(8, 0), (11, 256), (14, 2304), (30, 18688)]
+TOC = [(10, 0), (14, 1024), (22, 17408), (30, 4211712)]
+
+
+def pack_signed(v):
+    return 2 * v if v >= 0 else -2 * v - 1
+
+
+# --- Entropy coding ----------------------------------------------------------
+
+# Prefix codes with a fixed length for every symbol. Hybrid uint config
+# (split_exponent 0, no msb/lsb in token): token 0 is 0, token t codes values
+# in [2^(t-1), 2^t) with t-1 raw bits.
+CODE_BITS = 6
+ALPHABET = 1 << CODE_BITS
+CODE_LENGTH_ORDER = [1, 2, 3, 4, 0, 5, 17, 6, 16, 7, 8, 9, 10, 11, 12, 13, 14, 15]
+# Static code for the code length code lengths: value -> (bits, length)
+CODE_LENGTH_PREFIX = {0: (0, 2), 1: (7, 4), 2: (3, 3), 3: (2, 2), 4: (1, 2), 5: (15, 4)}
+
+
+def write_histograms(w, num_contexts):
+    """All contexts share one histogram of ALPHABET equally long codes"""
+    w.bool(False)  # no LZ77
+    if num_contexts > 1:
+        w.bool(True)  # simple context map
+        w.bits(0, 2)  # 0 bits per entry: everything maps to cluster 0
+    w.bool(True)  # prefix codes
+    w.bits(0, 4)  # split_exponent 0, so msb and lsb take no bits
+
+    # Alphabet size, as 1 + VarLenUint16
+    w.bool(True)
+    w.bits(CODE_BITS - 1, 4)
+    w.bits(ALPHABET - 1 - (1 << (CODE_BITS - 1)), CODE_BITS - 1)
+
+    # Complex prefix code. The code length code has two 1-bit symbols, 0 and
+    # CODE_BITS; reading stops once both are seen and the code space is full.
+    w.bits(0, 2)  # HSKIP
+    for symbol in CODE_LENGTH_ORDER[: CODE_LENGTH_ORDER.index(CODE_BITS) + 1]:
+        w.bits(*CODE_LENGTH_PREFIX[1 if symbol in (0, CODE_BITS) else 0])
+    for _ in range(ALPHABET):
+        w.bits(1, 1)  # canonical code 1 is the second symbol, CODE_BITS
+
+
+def reverse_bits(v, n):
+    return int(format(v, "0%db" % n)[::-1], 2)
+
+
+def write_uint(w, value):
+    if value == 0:
+        token, nbits, raw = 0, 0, 0
+    else:
+        nbits = value.bit_length() - 1
+        token, raw = nbits + 1, value - (1 << nbits)
+    w.bits(reverse_bits(token, CODE_BITS), CODE_BITS)
+    w.bits(raw, nbits)
+
+
+# --- Headers -----------------------------------------------------------------
+
+
+class Image:
+    """Planar integer channels; extra channels (alpha) come last"""
+
+    def __init__(self, width, height, channels):
+        self.width, self.height = width, height
+        self.channels = channels
+
+
+def write_size(w, width, height):
+    w.bool(False)  # not small
+    w.u32(height, SIZE)
+    w.bits(0, 3)  # no fixed aspect ratio
+    w.u32(width, SIZE)
+
+
+def write_image_header(w, width, height, gray=False, alpha=False, bits=8, float_samples=False,
+                       orientation=1, animation=None):
+    w.bits(0xFF, 8)
+    w.bits(0x0A, 8)
+    write_size(w, width, height)
+
+    w.bool(False)  # ImageMetadata.all_default
+    extra_fields = orientation != 1 or animation is not None
+    w.bool(extra_fields)
+    if extra_fields:
+        w.bits(orientation - 1, 3)
+        w.bool(False)  # intrinsic size
+        w.bool(False)  # preview
+        w.bool(animation is not None)
+        if animation is not None:
+            tps_numerator, tps_denominator, num_loops, timecodes = animation
+            w.u32(tps_numerator, [val(100), val(1000), (10, 1), (30, 1)])
+            w.u32(tps_denominator, [val(1), val(1001), (8, 1), (10, 1)])
+            w.u32(num_loops, [val(0), (3, 0), (16, 0), (32, 0)])
+            w.bool(timecodes)
+
+    # Bit depth
+    w.bool(float_samples)
+    if float_samples:
+        w.u32(bits, [val(32), val(16), val(24), (6, 1)])
+        w.bits(8 - 1, 4)  # exponent bits
+    else:
+        w.u32(bits, [val(8), val(10), val(12), (6, 1)])
+    w.bool(bits <= 12 and not float_samples)  # modular_16_bit_buffers
+
+    w.u32(1 if alpha else 0, [val(0), val(1), (4, 2), (12, 1)])
+    if alpha:
+        w.bool(True)  # 8-bit alpha, all default
+    w.bool(False)  # xyb_encoded
+
+    # Colour encoding: sRGB, or sRGB-like gray
+    if gray:
+        w.bool(False)  # all_default
+        w.bool(False)  # want_icc
+        w.enum(1)  # gray
+        w.enum(1)  # D65
+        w.bool(False)  # no gamma
+        w.enum(13)  # sRGB transfer function
+        w.enum(1)  # relative rendering intent
+    else:
+        w.bool(True)
+
+    if extra_fields:
+        w.bool(True)  # tone mapping, all default
+    w.u64_zero()  # extensions
+    w.bool(True)  # CustomTransformData, all default
+    w.pad_to_byte()
+
+
+def write_frame(w, image, num_extra, have_animation, duration=0, is_last=True, crop=None, name="",
+                timecode=None):
+    """Write a Modular frame; `crop` is (x0, y0) for a frame smaller than the canvas,
+    and `timecode` is required when the header declares timecodes"""
+    w.bool(False)  # all_default
+    w.u32(0, [val(0), val(1), val(2), val(3)])  # regular frame
+    w.enum(1)  # modular
+    w.u64_zero()  # flags
+    w.bool(False)  # no YCbCr
+    w.u32(1, [val(1), val(2), val(4), val(8)])  # upsampling
+    for _ in range(num_extra):
+        w.u32(1, [val(1), val(2), val(4), val(8)])
+    w.bits(1, 2)  # group_size_shift: 256x256 groups
+    w.u32(1, [val(1), val(2), val(3), (3, 4)])  # one pass
+
+    w.bool(crop is not None)
+    if crop is not None:
+        w.u32(pack_signed(crop[0]), CROP)
+        w.u32(pack_signed(crop[1]), CROP)
+        w.u32(image.width, CROP)
+        w.u32(image.height, CROP)
+
+    # Blending: replace, for the color channels and each extra channel
+    for _ in range(1 + num_extra):
+        w.u32(0, [val(0), val(1), val(2), (2, 3)])
+        if crop is not None:
+            w.u32(0, [val(0), val(1), val(2), val(3)])  # source
+
+    if have_animation:
+        w.u32(duration, [val(0), val(1), (8, 0), (32, 0)])
+        if timecode is not None:
+            w.bits(timecode, 32)
+    w.bool(is_last)
+    if not is_last:
+        w.bits(0, 2)  # save_as_reference
+        if crop is None and (not have_animation or duration == 0):
+            w.bool(False)  # save_before_color_transform
+
+    name = name.encode()
+    w.u32(len(name), [val(0), (4, 0), (5, 16), (10, 48)])
+    for b in name:
+        w.bits(b, 8)
+
+    # Loop filter: no Gaborish, no edge-preserving filter
+    w.bool(False)
+    w.bool(False)
+    w.bits(0, 2)
+    w.u64_zero()
+    w.u64_zero()  # extensions
+
+    # A single group: one TOC entry holding the whole frame
+    section = BitWriter()
+    write_modular(section, image)
+    section.pad_to_byte()
+    data = section.bytes()
+
+    w.bool(False)  # TOC not permuted
+    w.pad_to_byte()
+    w.u32(len(data), TOC)
+    w.pad_to_byte()
+    for b in data:
+        w.bits(b, 8)
+
+
+def gradient_predict(channel, width, x, y):
+    left = channel[y * width + x - 1] if x else (channel[(y - 1) * width + x] if y else 0)
+    top = channel[(y - 1) * width + x] if y else left
+    topleft = channel[(y - 1) * width + x - 1] if x and y else left
+    lo, hi = min(left, top), max(left, top)
+    if topleft < lo:
+        return hi
+    if topleft > hi:
+        return lo
+    return left + top - topleft
+
+
+def write_modular(w, image):
+    w.bool(True)  # LfChannelDequantization, all default
+    w.bool(True)  # global MA tree
+
+    # The tree is a single leaf: gradient predictor, no offset, multiplier 1
+    write_histograms(w, 6)
+    for token in (0, 5, 0, 0, 0):
+        write_uint(w, token)
+    write_histograms(w, 1)
+
+    # Group header: use the global tree, default weighted predictor, no transforms
+    w.bool(True)
+    w.bool(True)
+    w.u32(0, [val(0), val(1), (4, 2), (8, 18)])
+
+    for channel in image.channels:
+        for y in range(image.height):
+            for x in range(image.width):
+                value = channel[y * image.width + x]
+                write_uint(w, pack_signed(value - gradient_predict(channel, image.width, x, y)))
+
+
+# --- Sample images -----------------------------------------------------------
+
+
+def plane(fn, width=WIDTH, height=HEIGHT):
+    return [fn(x / (width - 1), y / (height - 1)) for y in range(height) for x in range(width)]
+
+
+def base_rgb():
+    """Smooth gradients with a sharp-edged square, as floats in [0, 1]"""
+
+    def square(x, y):
+        return 0.3 < x < 0.5 and 0.2 < y < 0.6
+
+    return [
+        plane(lambda x, y: 1.0 if square(x, y) else x),
+        plane(lambda x, y: 0.0 if square(x, y) else y * (1 - x)),
+        plane(lambda x, y: 0.2 if square(x, y) else 1 - y),
+    ]
+
+
+def base_alpha():
+    return plane(lambda x, y: 0.0 if x < 0.1 else min(1.0, y + 0.25))
+
+
+def quantize(channels, bits=8):
+    top = (1 << bits) - 1
+    return [[int(round(v * top)) for v in c] for c in channels]
+
+
+def float_bits(channels):
+    return [[struct.unpack("<I", struct.pack("<f", v))[0] for v in c] for c in channels]
+
+
+def gray_of(rgb):
+    return [(r + 2 * g + b) / 4 for r, g, b in zip(*rgb)]
+
+
+# Orientation as displayed: maps a displayed pixel to the stored one
+def oriented(channel, width, height, orientation):
+    t = {
+        1: (width, height, lambda x, y: (x, y)),
+        2: (width, height, lambda x, y: (width - 1 - x, y)),
+        3: (width, height, lambda x, y: (width - 1 - x, height - 1 - y)),
+        4: (width, height, lambda x, y: (x, height - 1 - y)),
+        5: (height, width, lambda x, y: (y, x)),
+        6: (height, width, lambda x, y: (y, height - 1 - x)),
+        7: (height, width, lambda x, y: (width - 1 - y, height - 1 - x)),
+        8: (height, width, lambda x, y: (width - 1 - y, x)),
+    }[orientation]
+    out_w, out_h, source = t
+    out = []
+    for y in range(out_h):
+        for x in range(out_w):
+            sx, sy = source(x, y)
+            out.append(channel[sy * width + sx])
+    return out, out_w, out_h
+
+
+# --- PNG references ----------------------------------------------------------
+
+
+def png_chunk(kind, data):
+    return struct.pack(">I", len(data)) + kind + data + struct.pack(">I", zlib.crc32(kind + data))
+
+
+def png_rows(samples, width, height, channels):
+    raw = bytearray()
+    for y in range(height):
+        raw.append(0)
+        raw.extend(samples[y * width * channels:(y + 1) * width * channels])
+    return zlib.compress(bytes(raw), 9)
+
+
+def write_png(path, frames, width, height, channels, delays=None):
+    """8-bit PNG; several frames make an APNG with `delays` in milliseconds"""
+    color_type = {1: 0, 2: 4, 3: 2, 4: 6}[channels]
+    out = b"\x89PNG\r\n\x1a\n"
+    out += png_chunk(b"IHDR", struct.pack(">IIBBBBB", width, height, 8, color_type, 0, 0, 0))
+    if len(frames) > 1:
+        out += png_chunk(b"acTL", struct.pack(">II", len(frames), 0))
+    sequence = 0
+    for i, samples in enumerate(frames):
+        if len(frames) > 1:
+            out += png_chunk(b"fcTL", struct.pack(">IIIIIHHBB", sequence, width, height, 0, 0,
+                                                   delays[i], 1000, 0, 0))
+            sequence += 1
+        data = png_rows(samples, width, height, channels)
+        if i == 0:
+            out += png_chunk(b"IDAT", data)
+        else:
+            out += png_chunk(b"fdAT", struct.pack(">I", sequence) + data)
+            sequence += 1
+    out += png_chunk(b"IEND", b"")
+    with open(path, "wb") as f:
+        f.write(out)
+
+
+def interleave(channels):
+    return bytes(v for px in zip(*channels) for v in px)
+
+
+def to_8bit(channels, bits=8):
+    top = (1 << bits) - 1
+    return [[int(round(v * 255 / top)) for v in c] for c in channels]
+
+
+# --- Fixtures ----------------------------------------------------------------
+
+
+def encode_still(path, channels, gray=False, alpha=False, bits=8, float_samples=False, orientation=1):
+    w = BitWriter()
+    write_image_header(w, WIDTH, HEIGHT, gray=gray, alpha=alpha, bits=bits,
+                       float_samples=float_samples, orientation=orientation)
+    write_frame(w, Image(WIDTH, HEIGHT, channels), int(alpha), False)
+    with open(path, "wb") as f:
+        f.write(w.bytes())
+
+
+def encode_animation(path, frames, durations, tps=(1000, 1), loops=0, timecodes=None):
+    """Animation of full RGB frames; `durations` are in ticks, `timecodes` are
+    packed SMPTE values, one per frame"""
+    w = BitWriter()
+    write_image_header(w, WIDTH, HEIGHT, animation=(tps[0], tps[1], loops, timecodes is not None))
+    for i, (channels, duration) in enumerate(zip(frames, durations)):
+        write_frame(w, Image(WIDTH, HEIGHT, channels), 0, True, duration=duration,
+                    is_last=i == len(frames) - 1, timecode=timecodes and timecodes[i])
+    with open(path, "wb") as f:
+        f.write(w.bytes())
+
+
+def generate():
+    golden = os.path.join(HERE, "golden")
+    truncated = os.path.join(HERE, "truncated")
+    animations = os.path.join(HERE, "animations")
+    for d in (golden, truncated, animations):
+        os.makedirs(d, exist_ok=True)
+
+    def out(name):
+        return os.path.join(golden, name)
+
+    rgb = quantize(base_rgb())
+    alpha = quantize([base_alpha()])[0]
+    gray = quantize([gray_of(base_rgb())])[0]
+
+    encode_still(out("gray.jxl"), [gray], gray=True)
+    write_png(out("gray.png"), [bytes(gray)], WIDTH, HEIGHT, 1)
+
+    encode_still(out("gray-alpha.jxl"), [gray, alpha], gray=True, alpha=True)
+    write_png(out("gray-alpha.png"), [interleave([gray, alpha])], WIDTH, HEIGHT, 2)
+
+    encode_still(out("rgb.jxl"), rgb)
+    write_png(out("rgb.png"), [interleave(rgb)], WIDTH, HEIGHT, 3)
+
+    encode_still(out("rgba.jxl"), rgb + [alpha], alpha=True)
+    write_png(out("rgba.png"), [interleave(rgb + [alpha])], WIDTH, HEIGHT, 4)
+
+    rgb16 = quantize(base_rgb(), 16)
+    encode_still(out("rgb-16bit.jxl"), rgb16, bits=16)
+    write_png(out("rgb-16bit.png"), [interleave(to_8bit(rgb16, 16))], WIDTH, HEIGHT, 3)
+
+    encode_still(out("rgb-float.jxl"), float_bits(base_rgb()), bits=32, float_samples=True)
+    write_png(out("rgb-float.png"), [interleave(quantize(base_rgb()))], WIDTH, HEIGHT, 3)
+
+    for n in range(2, 9):
+        encode_still(out("orientation-%d.jxl" % n), rgb, orientation=n)
+        shown = [oriented(c, WIDTH, HEIGHT, n) for c in rgb]
+        width, height = shown[0][1], shown[0][2]
+        write_png(out("orientation-%d.png" % n), [interleave([c for c, _, _ in shown])], width, height, 3)
+
+    # Three-frame animation: the image, turned 180 degrees and inverted
+    frames = [rgb, [oriented(c, WIDTH, HEIGHT, 3)[0] for c in rgb], [[255 - v for v in c] for c in rgb]]
+    encode_animation(out("animated.jxl"), frames, [100, 100, 100])
+    write_png(out("animated.png"), [interleave(f) for f in frames], WIDTH, HEIGHT, 3, [100] * 3)
+    shutil.copy(out("animated.jxl"), os.path.join(animations, "animated.jxl"))
+
+    # Tick rates that are not whole milliseconds
+    encode_animation(os.path.join(animations, "ntsc.jxl"), frames, [1, 2, 3], tps=(30000, 1001), loops=3)
+    encode_animation(os.path.join(animations, "seven-tps.jxl"), frames[:2], [1, 5], tps=(7, 1), loops=1)
+    # Timecodes 01:00:00:00 and 01:00:00:12, packed as hours, minutes, seconds, frames
+    encode_animation(os.path.join(animations, "timecodes.jxl"), frames[:2], [500, 500],
+                     timecodes=[0x01000000, 0x0100000C])
+
+    # Two layers: a full background, then a cropped layer replacing a square of it
+    overlay = [[255] * 100, [0] * 100, [0] * 100]  # 10x10 red
+    w = BitWriter()
+    write_image_header(w, WIDTH, HEIGHT)
+    write_frame(w, Image(WIDTH, HEIGHT, rgb), 0, False, is_last=False, name="background")
+    write_frame(w, Image(10, 10, overlay), 0, False, crop=(20, 5), name="overlay")
+    with open(out("layers.jxl"), "wb") as f:
+        f.write(w.bytes())
+    coalesced = [list(c) for c in rgb]
+    for c, o in zip(coalesced, overlay):
+        for y in range(10):
+            c[(5 + y) * WIDTH + 20:(5 + y) * WIDTH + 30] = o[y * 10:(y + 1) * 10]
+    write_png(out("layers.png"), [interleave(coalesced)], WIDTH, HEIGHT, 3)
+
+    # Files cut off in the header, the first frame and between frames
+    def cut(source, dest, length):
+        with open(out(source), "rb") as f:
+            data = f.read()
+        with open(os.path.join(truncated, dest), "wb") as f:
+            f.write(data[: length(len(data))])
+
+    cut("rgb.jxl", "rgb-header.jxl", lambda n: 16)
+    cut("rgb.jxl", "rgb-half.jxl", lambda n: n // 2)
+    cut("animated.jxl", "animated-third.jxl", lambda n: n // 3)
+    cut("animated.jxl", "animated-end.jxl", lambda n: n - 8)
+
+    # Inputs for tests/fuzz_regressions.rs: frames whose size or position does
+    # not match the canvas, the shape of input that once left channels or the
+    # interleaved RGB buffer shorter than the image
+    fuzz = os.path.join(HERE, "fuzz")
+    os.makedirs(fuzz, exist_ok=True)
+
+    def layered(name, frames, width=WIDTH, height=HEIGHT, orientation=1, animation=None):
+        """`frames` are (channels, width, height, crop) with crop None for the full canvas"""
+        w = BitWriter()
+        write_image_header(w, width, height, orientation=orientation, animation=animation)
+        for i, (channels, frame_width, frame_height, crop) in enumerate(frames):
+            write_frame(w, Image(frame_width, frame_height, channels), 0, animation is not None,
+                        duration=10, is_last=i == len(frames) - 1, crop=crop)
+        with open(os.path.join(fuzz, name), "wb") as f:
+            f.write(w.bytes())
+
+    background = (rgb, WIDTH, HEIGHT, None)
+    layered("cropped-frame-animation.jxl", [background, (overlay, 10, 10, (20, 5))], animation=(1000, 1, 0, False))
+    layered("cropped-frame-past-edge.jxl", [background, (overlay, 10, 10, (40, 25))])
+    layered("cropped-frame-before-origin.jxl", [background, (overlay, 10, 10, (-5, -5))])
+    layered("cropped-frame-transposed.jxl", [background, (overlay, 10, 10, (20, 5))], orientation=6)
+    # Only a small frame, on a canvas far above the pixel limit
+    layered("huge-canvas.jxl", [(overlay, 10, 10, (0, 0))], width=100000, height=100000)
+
+
+def read_pnm(path):
+    """Samples of an 8-bit PGM, PPM or PAM file, with its width, height and depth"""
+    with open(path, "rb") as f:
+        data = f.read()
+    magic = data[:2]
+    if magic == b"P7":
+        header, _, pixels = data.partition(b"ENDHDR\n")
+        fields = dict(line.split(b" ", 1) for line in header.split(b"\n")[1:] if b" " in line)
+        width, height, depth = int(fields[b"WIDTH"]), int(fields[b"HEIGHT"]), int(fields[b"DEPTH"])
+    else:
+        parts = data.split(maxsplit=4)
+        width, height, pixels = int(parts[1]), int(parts[2]), parts[4]
+        depth = 1 if magic == b"P5" else 3
+    return pixels[: width * height * depth], width, height, depth
+
+
+def read_png(path):
+    """First frame of a PNG written by `write_png` (filter type 0 only)"""
+    with open(path, "rb") as f:
+        data = f.read()
+    pos, idat = 8, b""
+    while pos < len(data):
+        length, kind = struct.unpack(">I4s", data[pos:pos + 8])
+        body = data[pos + 8:pos + 8 + length]
+        if kind == b"IHDR":
+            width, height, _, color_type = struct.unpack(">IIBB", body[:10])
+        elif kind == b"IDAT":
+            idat += body
+        pos += 12 + length
+    depth = {0: 1, 4: 2, 2: 3, 6: 4}[color_type]
+    raw = zlib.decompress(idat)
+    stride = width * depth + 1
+    pixels = b"".join(raw[y * stride + 1:(y + 1) * stride] for y in range(height))
+    return pixels, width, height, depth
+
+
+def check():
+    """Decode every golden fixture with djxl and compare it with its reference"""
+    djxl = shutil.which("djxl")
+    if djxl is None:
+        sys.exit("djxl not found")
+    golden = os.path.join(HERE, "golden")
+    failed = False
+    with tempfile.TemporaryDirectory() as work:
+        for name in sorted(os.listdir(golden)):
+            if not name.endswith(".jxl"):
+                continue
+            stem = name[:-4]
+            decoded = os.path.join(work, stem + ".pam")
+            result = subprocess.run([djxl, os.path.join(golden, name), decoded, "--bits_per_sample=8"],
+                                    capture_output=True, text=True)
+            if result.returncode != 0:
+                status = result.stderr.strip()
+            else:
+                actual = read_pnm(decoded)
+                expected = read_png(os.path.join(golden, stem + ".png"))
+                if actual[1:] != expected[1:]:
+                    status = "size %r, expected %r" % (actual[1:], expected[1:])
+                else:
+                    error = max((abs(a - b) for a, b in zip(actual[0], expected[0])), default=0)
+                    status = "ok" if error <= 1 else "differs by up to %d" % error
+            print("%-20s %s" % (name, status))
+            failed |= status != "ok"
+    if failed:
+        sys.exit(1)
+
+
+if __name__ == "__main__":
+    parser = argparse.ArgumentParser(description=__doc__.splitlines()[0])
+    parser.add_argument("--check", action="store_true", help="decode the fixtures with djxl")
+    args = parser.parse_args()
+    check() if args.check else generate()
@@ -0,0 +1,30 @@
+//! Inputs that once crashed the `decode` fuzz target (see fuzz/README.md)
+//!
+//! Each file in `tests/fixtures/fuzz` must be rejected with an error or
+//! decoded, but never panic, and so must its first half.
+
+use jxl_ui::decoder::worker::{catch_panic, decode_jxl_bytes, DecodeOptions, DecoderPanic};
+
+const FUZZ_DIR: &str = "tests/fixtures/fuzz";
+
+#[test]
+fn test_fuzz_regressions() {
+    let entries = std::fs::read_dir(FUZZ_DIR)
+        .unwrap_or_else(|e| panic!("{}: {}; run tests/fixtures/generate.py", FUZZ_DIR, e));
+
+    let mut count = 0;
+    for path in entries.flatten().map(|e| e.path()) {
+        if path.extension().and_then(|e| e.to_str()) == Some("md") {
+            continue;
+        }
+        let data = std::fs::read(&path).unwrap();
+        let options = DecodeOptions { pixel_limit: Some(4_000_000), ..DecodeOptions::default() };
+        for input in [&data[..], &data[..data.len() / 2]] {
+            if let Err(e) = catch_panic(|| decode_jxl_bytes(input, &options)) {
+                assert!(e.downcast_ref::<DecoderPanic>().is_none(), "{:?} ({} bytes): {}", path, input.len(), e);
+            }
+        }
+        count += 1;
+    }
+    assert!(count > 0, "no inputs in {}; run tests/fixtures/generate.py", FUZZ_DIR);
+}
@@ -0,0 +1,222 @@
+//! Golden decode tests: JXL fixtures decoded through `decode_jxl` and compared
+//! with PNG references.
+//!
+//! Fixtures live in `tests/fixtures/golden` and are created by
+//! `tests/fixtures/generate.py`. Missing fixtures are skipped with a warning,
+//! unless `JXL_UI_REQUIRE_FIXTURES` is set, in which case they fail the test.
+
+use image::AnimationDecoder;
+use jxl_ui::decoder::container::FileKind;
+use jxl_ui::decoder::structure::inspect_file;
+use jxl_ui::decoder::worker::{catch_panic, decode_jxl, decode_layers, DecodeOptions, DecoderPanic};
+use jxl_ui::decoder::{DecodeResult, DecodedFrame};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+const GOLDEN_DIR: &str = "tests/fixtures/golden";
+const TRUNCATED_DIR: &str = "tests/fixtures/truncated";
+
+enum Kind {
+    Still,
+    Animation { frames: usize },
+    /// Coalesced like a still image, with this many layers when decoded separately
+    Layers { layers: usize },
+}
+
+struct Fixture {
+    name: &'static str,
+    kind: Kind,
+    /// Largest allowed difference per 8-bit channel
+    max_error: u8,
+}
+
+const fn still(name: &'static str, max_error: u8) -> Fixture {
+    Fixture { name, kind: Kind::Still, max_error }
+}
+
+const FIXTURES: &[Fixture] = &[
+    still("gray", 2),
+    still("gray-alpha", 2),
+    still("rgb", 2),
+    still("rgba", 2),
+    still("rgb-16bit", 2),
+    still("rgb-float", 2),
+    still("orientation-2", 2),
+    still("orientation-3", 2),
+    still("orientation-4", 2),
+    still("orientation-5", 2),
+    still("orientation-6", 2),
+    still("orientation-7", 2),
+    still("orientation-8", 2),
+    Fixture { name: "animated", kind: Kind::Animation { frames: 3 }, max_error: 2 },
+    Fixture { name: "layers", kind: Kind::Layers { layers: 2 }, max_error: 2 },
+];
+
+fn require_fixtures() -> bool {
+    std::env::var_os("JXL_UI_REQUIRE_FIXTURES").is_some()
+}
+
+/// Path of a fixture file, or `None` (after reporting it) if it is missing
+fn fixture_path(dir: &str, file: &str) -> Option<PathBuf> {
+    let path = Path::new(dir).join(file);
+    if path.exists() {
+        return Some(path);
+    }
+    assert!(!require_fixtures(), "missing fixture {:?}", path);
+    eprintln!("skipping missing fixture {:?} (see tests/fixtures/README.md)", path);
+    None
+}
+
+/// Reference frames as premultiplied RGBA8, matching the decoder's output
+fn load_reference(path: &Path) -> Vec<(u32, u32, Vec<u8>)> {
+    let reader = BufReader::new(File::open(path).unwrap());
+    let decoder = image::codecs::png::PngDecoder::new(reader).unwrap();
+
+    let frames: Vec<image::RgbaImage> = if decoder.is_apng().unwrap() {
+        decoder
+            .apng()
+            .unwrap()
+            .into_frames()
+            .map(|f| f.unwrap().into_buffer())
+            .collect()
+    } else {
+        vec![image::DynamicImage::from_decoder(decoder).unwrap().to_rgba8()]
+    };
+
+    frames
+        .into_iter()
+        .map(|img| {
+            let (width, height) = img.dimensions();
+            let mut rgba = img.into_raw();
+            for px in rgba.chunks_exact_mut(4) {
+                let a = px[3] as u32;
+                for c in &mut px[..3] {
+                    *c = ((*c as u32 * a + 127) / 255) as u8;
+                }
+            }
+            (width, height, rgba)
+        })
+        .collect()
+}
+
+fn assert_matches(name: &str, frame: &DecodedFrame, reference: &(u32, u32, Vec<u8>), max_error: u8) {
+    let (width, height, expected) = reference;
+    assert_eq!((frame.width, frame.height), (*width, *height), "{}: size", name);
+
+    let worst = frame
+        .rgba_data
+        .iter()
+        .zip(expected)
+        .enumerate()
+        .map(|(i, (&a, &b))| (a.abs_diff(b), i))
+        .max()
+        .unwrap_or((0, 0));
+
+    let (error, index) = worst;
+    let pixel = index / 4;
+    assert!(
+        error <= max_error,
+        "{}: channel {} of pixel ({}, {}) differs by {} (allowed {})",
+        name,
+        index % 4,
+        pixel % *width as usize,
+        pixel / *width as usize,
+        error,
+        max_error
+    );
+}
+
+#[test]
+fn test_golden_fixtures() {
+    for fixture in FIXTURES {
+        let (Some(jxl), Some(png)) = (
+            fixture_path(GOLDEN_DIR, &format!("{}.jxl", fixture.name)),
+            fixture_path(GOLDEN_DIR, &format!("{}.png", fixture.name)),
+        ) else {
+            continue;
+        };
+
+        let reference = load_reference(&png);
+        let result = catch_panic(|| decode_jxl(&jxl, &DecodeOptions::default()))
+            .unwrap_or_else(|e| panic!("{}: {}", fixture.name, e));
+
+        match (&fixture.kind, result) {
+            (Kind::Still | Kind::Layers { .. }, DecodeResult::SingleFrame { frame, metadata }) => {
+                assert!(!metadata.has_animation, "{}: reported as animation", fixture.name);
+                assert_matches(fixture.name, &frame, &reference[0], fixture.max_error);
+            }
+            (Kind::Animation { frames: expected }, DecodeResult::Animation { frames, metadata }) => {
+                assert_eq!(frames.len(), *expected, "{}: frame count", fixture.name);
+                assert_eq!(metadata.frame_count, *expected, "{}: metadata frame count", fixture.name);
+                assert_eq!(reference.len(), *expected, "{}: reference frame count", fixture.name);
+                for (i, (frame, reference)) in frames.iter().zip(&reference).enumerate() {
+                    let name = format!("{} frame {}", fixture.name, i);
+                    assert_matches(&name, frame, reference, fixture.max_error);
+                }
+            }
+            _ => panic!("{}: decoded as the wrong kind of image", fixture.name),
+        }
+
+        if let Kind::Layers { layers } = fixture.kind {
+            let decoded = decode_layers(&jxl, &DecodeOptions::default()).unwrap();
+            assert_eq!(decoded.len(), layers, "{}: layer count", fixture.name);
+        }
+    }
+}
+
+#[test]
+fn test_truncated_fixtures() {
+    let Ok(entries) = std::fs::read_dir(TRUNCATED_DIR) else {
+        assert!(!require_fixtures(), "missing {}", TRUNCATED_DIR);
+        return;
+    };
+
+    for path in entries.flatten().map(|e| e.path()) {
+        if path.extension().and_then(|e| e.to_str()) != Some("jxl") {
+            continue;
+        }
+        match catch_panic(|| decode_jxl(&path, &DecodeOptions::default())) {
+            Ok(_) => panic!("{:?}: truncated file decoded successfully", path),
+            Err(e) => assert!(e.downcast_ref::<DecoderPanic>().is_none(), "{:?}: {}", path, e),
+        }
+    }
+}
+
+#[test]
+fn test_inspect_golden_fixtures() {
+    for fixture in FIXTURES {
+        let Some(jxl) = fixture_path(GOLDEN_DIR, &format!("{}.jxl", fixture.name)) else {
+            continue;
+        };
+
+        let structure = inspect_file(&jxl).unwrap_or_else(|e| panic!("{}: {}", fixture.name, e));
+        assert_eq!(structure.frames_error, None, "{}: frame headers", fixture.name);
+        assert_eq!(structure.kind, FileKind::Codestream, "{}: kind", fixture.name);
+        assert_eq!(structure.codestream_size, structure.file_size, "{}: codestream size", fixture.name);
+
+        let frames = match fixture.kind {
+            Kind::Still => 1,
+            Kind::Animation { frames } => frames,
+            Kind::Layers { layers } => layers,
+        };
+        assert_eq!(structure.frames.len(), frames, "{}: frame count", fixture.name);
+    }
+}
+
+#[test]
+fn test_inspect_truncated_fixtures() {
+    let Ok(entries) = std::fs::read_dir(TRUNCATED_DIR) else {
+        assert!(!require_fixtures(), "missing {}", TRUNCATED_DIR);
+        return;
+    };
+
+    for path in entries.flatten().map(|e| e.path()) {
+        if path.extension().and_then(|e| e.to_str()) != Some("jxl") {
+            continue;
+        }
+        // The box layout is still reported; walking the frames stops with an error
+        let structure = inspect_file(&path).unwrap_or_else(|e| panic!("{:?}: {}", path, e));
+        assert!(structure.frames_error.is_some(), "{:?}: truncation not reported", path);
+    }
+}